@@ -1,4 +1,9 @@
 use clap::{Args, Parser, Subcommand};
+use raf::dictionary::WordKind;
+use raf::output::{OutputLocation, OutputPlan};
+use raf::report::ReportFormat;
+use raf::{OverlapPolicy, Strategy};
+use std::str::FromStr;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about, name = "raf")]
@@ -6,6 +11,7 @@ pub struct Opts {
     /// Two options: `folder` or `file` to specify whether raf should redact files in a folder or a single file
     /// Example: `raf folder ./tests/test_files -t phone emails -r` means to redact files in `./tests/test_files` with regex types (`-t`) `phone` and `emails` recursively (`-r`)
     /// Example: `raf file ./tests/test_files/docx_1.docx`
+    /// Example: `kubectl logs my-pod | raf file - -t emails --mapping logs-unredact.json`
     #[clap(subcommand)]
     pub cmd: FileOrFolder,
 }
@@ -16,10 +22,109 @@ pub enum FileOrFolder {
     /// Example: `raf folder ./tests/test_files -t phone emails -r` means to redact files in `./tests/test_files` with regex types (`-t`) `phone` and `emails` recursively (`-r`)
     #[clap(name = "folder")]
     Folder(FolderOpts),
-    /// Redact a single file with a specified file path.
+    /// Redact a single file with a specified file path, or `-` to redact stdin to stdout.
     /// Example: `raf file ./tests/test_files/docx_1.docx`
     #[clap(name = "file")]
     File(FileOpts),
+    /// Report what redacting a file or folder would replace, without writing anything but the report.
+    /// Example: `raf scan ./tests/test_files -t phone emails -r --report-format csv -o findings.csv`
+    #[clap(name = "scan")]
+    Scan(ScanOpts),
+    /// Restore the original of a redacted file from its `-unredact.json` mapping or a mapping store, checking that every token is still in place.
+    /// Example: `raf unredact ./tests/test_files/redacted/file1.txt`
+    #[clap(name = "unredact")]
+    Unredact(UnredactOpts),
+    /// Decrypt tokens of the `fpe` strategy with its key, without a mapping.
+    /// Example: `raf decrypt --key-file ~/.raf/key M7990119Z`
+    #[clap(name = "decrypt")]
+    Decrypt(DecryptOpts),
+    /// Inspect unredact mappings.
+    /// Example: `raf mapping show ./tests/test_files/redacted/file1-unredact.vault`
+    #[clap(name = "mapping", subcommand)]
+    Mapping(MappingCmd),
+    /// Inspect the redaction patterns.
+    /// Example: `raf patterns list --patterns ./my-patterns.json`
+    #[clap(name = "patterns", subcommand)]
+    Patterns(PatternsCmd),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PatternsCmd {
+    /// List the patterns, built-in and from patterns files, with where each came from.
+    #[clap(name = "list")]
+    List(PatternOpts),
+}
+
+#[derive(Args, Debug)]
+pub struct ListOpts {
+    /// A file of values never to redact, one per line, or a regex after `re:`. Prefix it with `TYPE=` to only allow the values for the patterns of that type. Can be given several times.
+    /// Example: `--allow-list phone=./switchboard.txt`.
+    #[clap(long, value_name = "[TYPE=]FILE", multiple_occurrences = true)]
+    pub allow_list: Vec<ListArg>,
+
+    /// A file of values always to redact, one per line, or a regex after `re:`, as type `TYPE`, `denied` by default. Can be given several times.
+    /// Example: `--deny-list name=./customers.txt`.
+    #[clap(long, value_name = "[TYPE=]FILE", multiple_occurrences = true)]
+    pub deny_list: Vec<ListArg>,
+
+    /// A word list of first names, surnames or organisations, one per line, to redact as type `name` or `organisation`, optionally tagged with a locale. Can be given several times.
+    /// Example: `--dictionary surname:en-SG=./surnames.txt`.
+    #[clap(long, value_name = "KIND[:LOCALE]=FILE", multiple_occurrences = true)]
+    pub dictionary: Vec<DictionaryArg>,
+
+    /// Only use the word lists of these locales, or of regions of them, and those with no locale. Defaults to all.
+    /// Example: `--locale en zh-SG`.
+    #[clap(long, multiple_values = true)]
+    pub locale: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct PatternOpts {
+    /// A patterns file to add to the built-in patterns and those of the user config directory. Can be given several times, later files replacing the patterns with the same `id` in earlier ones.
+    /// Example: `--patterns ./my-patterns.json`.
+    #[clap(
+        long = "patterns",
+        value_name = "FILE",
+        parse(from_os_str),
+        multiple_occurrences = true
+    )]
+    pub files: Vec<std::path::PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MappingCmd {
+    /// Print a mapping as JSON, decrypting it if it is encrypted.
+    #[clap(name = "show")]
+    Show(MappingShowOpts),
+    /// Look up a mapping store, either the original value of a token or where a value was redacted.
+    /// Example: `raf mapping lookup ./mappings.jsonl --token '[REDACTED:3f9a0c1e7b2d4a65]'`
+    #[clap(name = "lookup")]
+    Lookup(MappingLookupOpts),
+}
+
+#[derive(Args, Debug)]
+pub struct MappingLookupOpts {
+    /// `path` of the mapping store written with `--mapping-store`.
+    #[clap(parse(from_os_str), required = true)]
+    pub path: std::path::PathBuf,
+
+    /// Token to find the original value of.
+    #[clap(long, required_unless_present = "value", conflicts_with = "value")]
+    pub token: Option<String>,
+
+    /// Original value to find the tokens and locations of.
+    #[clap(long)]
+    pub value: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct MappingShowOpts {
+    /// `path` of the `-unredact.json` or `-unredact.vault` mapping.
+    #[clap(parse(from_os_str), required = true)]
+    pub path: std::path::PathBuf,
+
+    #[clap(flatten)]
+    pub vault: VaultOpts,
 }
 
 #[derive(Args, Debug)]
@@ -34,16 +139,31 @@ pub struct FolderOpts {
     #[clap(short, long, required = true, multiple_values = true)]
     pub types: Vec<String>,
 
+    #[clap(flatten)]
+    pub patterns: PatternOpts,
+
+    #[clap(flatten)]
+    pub lists: ListOpts,
+
     /// Whether raf should redact subdirectories recursively. Defaults to `false`, which means raf will only redact files found in the directory specified.
     /// Example: `-t emails -r`, `-r` specifies to redact all sub-directories recursively.
     #[clap(short, long, takes_value = false)]
     pub recursive: bool,
+
+    #[clap(flatten)]
+    pub redact: RedactOpts,
+
+    #[clap(flatten)]
+    pub review: ReviewOpts,
+
+    #[clap(flatten)]
+    pub output: OutputOpts,
 }
 
 #[derive(Args, Debug)]
 pub struct FileOpts {
-    /// `path` of the directory in which all files should be redacted.
-    /// Example, on Windows: `./tests/test_files`.
+    /// `path` of the file to be redacted, or `-` to read it from stdin and write the redacted file to stdout.
+    /// Example, on Windows: `./tests/test_files/file1.txt`.
     #[clap(parse(from_os_str), required = true)]
     pub path: std::path::PathBuf,
 
@@ -51,4 +171,316 @@ pub struct FileOpts {
     /// Example: `-t phone emails`, specifies to redact all text that matches the regexes of `phone` and `emails`.
     #[clap(short, long, required = true, multiple_values = true)]
     pub types: Vec<String>,
+
+    #[clap(flatten)]
+    pub patterns: PatternOpts,
+
+    #[clap(flatten)]
+    pub lists: ListOpts,
+
+    /// Format of the document read from stdin, e.g. `docx`. Only used when `path` is `-`; defaults to `txt`.
+    #[clap(long, default_value = "txt")]
+    pub format: String,
+
+    /// Where to write the unredact mapping when `path` is `-`. Without it, no mapping is written.
+    /// Example: `--mapping ./logs-unredact.json`.
+    #[clap(long, parse(from_os_str))]
+    pub mapping: Option<std::path::PathBuf>,
+
+    #[clap(flatten)]
+    pub redact: RedactOpts,
+
+    #[clap(flatten)]
+    pub review: ReviewOpts,
+
+    #[clap(flatten)]
+    pub output: OutputOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct ScanOpts {
+    /// `path` of the file or directory to scan.
+    /// Example: `./tests/test_files`.
+    #[clap(parse(from_os_str), required = true)]
+    pub path: std::path::PathBuf,
+
+    /// The types of text to look for.
+    /// Example: `-t phone emails`.
+    #[clap(short, long, required = true, multiple_values = true)]
+    pub types: Vec<String>,
+
+    #[clap(flatten)]
+    pub patterns: PatternOpts,
+
+    #[clap(flatten)]
+    pub lists: ListOpts,
+
+    /// Whether raf should scan subdirectories recursively.
+    #[clap(short, long, takes_value = false)]
+    pub recursive: bool,
+
+    /// How to choose between overlapping matches of different patterns, as for redaction.
+    #[clap(long, default_value = "longest")]
+    pub overlap: OverlapPolicy,
+
+    /// Score a match needs to be reported, as for redaction.
+    #[clap(long, default_value = "0.5")]
+    pub min_score: f64,
+
+    /// Format of the report: `table`, `json`, `csv` or `sarif`, the latter for code review tools that read SARIF 2.1.0.
+    #[clap(long, default_value = "table")]
+    pub report_format: ReportFormat,
+
+    /// How many locations of each type to list per file, with the matched values masked.
+    #[clap(long, default_value = "3")]
+    pub samples: usize,
+
+    /// Where to write the report. Defaults to stdout.
+    /// Example: `--output ./findings.json`.
+    #[clap(short, long, parse(from_os_str))]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Exit with an error if anything was found, after writing the report, e.g. to fail a CI job.
+    #[clap(long, takes_value = false)]
+    pub fail_on_findings: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct UnredactOpts {
+    /// `path` of the redacted file.
+    /// Example: `./tests/test_files/redacted/file1.txt`.
+    #[clap(parse(from_os_str), required = true)]
+    pub path: std::path::PathBuf,
+
+    /// The mapping written when the file was redacted. Defaults to the `-unredact.json` file next to it.
+    /// Example: `--mapping ./mappings/file1-unredact.json`.
+    #[clap(short, long, parse(from_os_str))]
+    pub mapping: Option<std::path::PathBuf>,
+
+    /// The mapping store the file's mapping was appended to with `--mapping-store`, instead of `--mapping`.
+    /// Example: `--mapping-store ./mappings.jsonl`.
+    #[clap(long, parse(from_os_str), conflicts_with = "mapping")]
+    pub mapping_store: Option<std::path::PathBuf>,
+
+    /// The path of the file when it was redacted, as recorded in the mapping store. Defaults to the only recorded file with the same name.
+    /// Example: `--source ./tests/test_files/file1.txt`.
+    #[clap(long, parse(from_os_str), requires = "mapping-store")]
+    pub source: Option<std::path::PathBuf>,
+
+    /// Where to write the restored file. Defaults to an `unredacted` folder next to the redacted file.
+    /// Example: `--output ./file1.txt`.
+    #[clap(short, long, parse(from_os_str))]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Overwrite the restored file if it already exists.
+    #[clap(short, long, takes_value = false)]
+    pub force: bool,
+
+    #[clap(flatten)]
+    pub vault: VaultOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct RedactOpts {
+    /// How to choose between overlapping matches of different patterns: `longest` (the longest match wins), `first` (the pattern listed first in `patterns.json` wins) or `priority` (the pattern with the highest priority wins).
+    /// Example: `--overlap first`.
+    #[clap(long, default_value = "longest")]
+    pub overlap: OverlapPolicy,
+
+    /// Score from 0 to 1 a match needs to be redacted. Matches score the `score` of their pattern, 1 by default, raised by a positive keyword of the pattern nearby; a negative keyword nearby suppresses the match.
+    /// Example: `--min-score 0.7`.
+    #[clap(long, default_value = "0.5")]
+    pub min_score: f64,
+
+    /// How matches are replaced, either for all types or as `<type>=<strategy>` for one type. Strategies are `random` (the default), `hmac[:N]`, `fake`, `fpe`, `label[:TEXT]`, `mask`, `keep-last:N`, `blackout[:N]`, `counter` and `remove`.
+    /// A strategy given for a type overrides the `strategy` of its patterns in `patterns.json`, which overrides the strategy given for all types.
+    /// Example: `--strategy label --strategy phone=keep-last:4`.
+    #[clap(short, long, multiple_occurrences = true)]
+    pub strategy: Vec<StrategyArg>,
+
+    #[clap(flatten)]
+    pub key: KeyOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct KeyOpts {
+    /// File holding the secret key of the `hmac` and `fpe` strategies. A trailing newline is ignored.
+    /// Example: `--strategy hmac --key-file ~/.raf/key`.
+    #[clap(long, parse(from_os_str), conflicts_with = "key-env")]
+    pub key_file: Option<std::path::PathBuf>,
+
+    /// Environment variable holding the secret key of the `hmac` and `fpe` strategies, when no `--key-file` is given.
+    /// Example: `--key-env PROJECT_RAF_KEY`.
+    #[clap(long, default_value = "RAF_KEY")]
+    pub key_env: String,
+}
+
+#[derive(Args, Debug)]
+pub struct DecryptOpts {
+    /// Tokens written by the `fpe` strategy.
+    /// Example: `M7990119Z`.
+    #[clap(required = true)]
+    pub tokens: Vec<String>,
+
+    #[clap(flatten)]
+    pub key: KeyOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct ReviewOpts {
+    /// Write an HTML report of the run to this file, showing each redaction in context before and after, with summary statistics and errors. The report holds the original values unless `--mask-originals` is given.
+    /// Example: `--html-report ./review.html`.
+    #[clap(long, parse(from_os_str))]
+    pub html_report: Option<std::path::PathBuf>,
+
+    /// Mask the original values and the text around them in the HTML report, so that it can be shared.
+    #[clap(long, takes_value = false, requires = "html-report")]
+    pub mask_originals: bool,
+}
+
+/// A `--strategy` argument, optionally restricted to one type.
+#[derive(Debug, Clone)]
+pub struct StrategyArg {
+    pub type_name: Option<String>,
+    pub strategy: Strategy,
+}
+
+impl FromStr for StrategyArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (type_name, strategy) = match s.split_once('=') {
+            Some((type_name, strategy)) => (Some(type_name.to_owned()), strategy),
+            None => (None, s),
+        };
+        Ok(StrategyArg {
+            type_name,
+            strategy: strategy.parse()?,
+        })
+    }
+}
+
+/// An `--allow-list` or `--deny-list` argument, optionally restricted to one
+/// type.
+#[derive(Debug, Clone)]
+pub struct ListArg {
+    pub type_name: Option<String>,
+    pub path: std::path::PathBuf,
+}
+
+impl FromStr for ListArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (type_name, path) = match s.split_once('=') {
+            Some((type_name, path)) => (Some(type_name.to_owned()), path),
+            None => (None, s),
+        };
+        Ok(ListArg {
+            type_name,
+            path: path.into(),
+        })
+    }
+}
+
+/// A `--dictionary` argument.
+#[derive(Debug, Clone)]
+pub struct DictionaryArg {
+    pub kind: WordKind,
+    pub locale: Option<String>,
+    pub path: std::path::PathBuf,
+}
+
+impl FromStr for DictionaryArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((spec, path)) = s.split_once('=') else {
+            return Err(format!("expected `KIND[:LOCALE]=FILE`, got `{s}`"));
+        };
+        let (kind, locale) = match spec.split_once(':') {
+            Some((kind, locale)) => (kind, Some(locale.to_owned())),
+            None => (spec, None),
+        };
+        Ok(DictionaryArg {
+            kind: kind.parse()?,
+            locale,
+            path: path.into(),
+        })
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct OutputOpts {
+    /// Directory to write the redacted files to, mirroring the directory tree of the input. Defaults to a `redacted` folder next to each file.
+    /// Example: `--output ./redacted_share`.
+    #[clap(short, long, parse(from_os_str), conflicts_with = "in-place")]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Overwrite the input files with their redacted versions.
+    #[clap(long, takes_value = false)]
+    pub in_place: bool,
+
+    /// With `--in-place`, keep a copy of each original file with this suffix appended to its name.
+    /// Example: `--in-place --backup-suffix .orig`.
+    #[clap(long, requires = "in-place")]
+    pub backup_suffix: Option<String>,
+
+    /// Directory to write the `-unredact.json` mappings to, mirroring the directory tree of the input. Defaults to the directory of each redacted file.
+    /// Example: `--mapping-dir ./mappings`.
+    #[clap(long, parse(from_os_str))]
+    pub mapping_dir: Option<std::path::PathBuf>,
+
+    /// Overwrite existing redacted files, mappings and backups.
+    #[clap(short, long, takes_value = false)]
+    pub force: bool,
+
+    /// Append the mappings of all files to this JSON Lines store instead of writing one `-unredact.json` per file. Each original value is stored once and linked to every file and location it was redacted in. The store is always plain text, readable by its owner only, and cannot be encrypted with `--encrypt-mapping`.
+    /// Example: `--mapping-store ./mappings.jsonl`.
+    #[clap(long, parse(from_os_str), conflicts_with_all = &["mapping-dir", "encrypt-mapping"])]
+    pub mapping_store: Option<std::path::PathBuf>,
+
+    /// Encrypt the mappings into `-unredact.vault` files readable by their owner only, with the passphrase or key file given below. Not available with `--mapping-store`, which is always plain text.
+    /// Example: `--encrypt-mapping --vault-key-file ~/.raf/vault.key`.
+    #[clap(long, takes_value = false)]
+    pub encrypt_mapping: bool,
+
+    #[clap(flatten)]
+    pub vault: VaultOpts,
+}
+
+/// The secret of encrypted mappings.
+#[derive(Args, Debug)]
+pub struct VaultOpts {
+    /// Key file of encrypted mappings. A trailing newline is ignored.
+    /// Example: `--vault-key-file ~/.raf/vault.key`.
+    #[clap(long, parse(from_os_str), conflicts_with = "vault-passphrase-env")]
+    pub vault_key_file: Option<std::path::PathBuf>,
+
+    /// Environment variable holding the passphrase of encrypted mappings, when no `--vault-key-file` is given.
+    /// Example: `--vault-passphrase-env PROJECT_VAULT_PASSPHRASE`.
+    #[clap(long, default_value = "RAF_VAULT_PASSPHRASE")]
+    pub vault_passphrase_env: String,
+}
+
+impl OutputOpts {
+    /// The output plan for inputs found under `input_root`, without a vault
+    /// or store; see `--encrypt-mapping` and `--mapping-store`.
+    pub fn plan(self, input_root: std::path::PathBuf) -> OutputPlan {
+        let location = match (self.output, self.in_place) {
+            (Some(root), _) => OutputLocation::Mirror(root),
+            (None, true) => OutputLocation::InPlace {
+                backup_suffix: self.backup_suffix,
+            },
+            (None, false) => OutputLocation::Sibling,
+        };
+        OutputPlan {
+            input_root,
+            location,
+            mapping_dir: self.mapping_dir,
+            force: self.force,
+            vault: None,
+            store: None,
+        }
+    }
 }
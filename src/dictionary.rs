@@ -0,0 +1,243 @@
+//! Personal names and organisations found in word lists, which no regex
+//! can describe.
+//!
+//! Word lists are files of one entry per line, with `#` for comments, of
+//! first names, surnames or organisations, optionally tagged with a locale
+//! such as `en-SG`. Entries match regardless of ASCII case, as whole words
+//! starting with a capital letter. Consecutive names are found as one full
+//! name, e.g. `Tan Ah Kow`, and a single name at the start of a sentence,
+//! more often a word like `May` or `Will`, is skipped.
+
+use crate::error::{RafError, Result};
+use crate::lists;
+use crate::matcher::Detector;
+use aho_corasick::AhoCorasick;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Type of the names found by [`Dictionary::names`].
+pub const NAME_TYPE: &str = "name";
+
+/// Type of the organisations found by [`Dictionary::organisations`].
+pub const ORGANISATION_TYPE: &str = "organisation";
+
+/// What the entries of a [`WordList`] are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordKind {
+    FirstName,
+    Surname,
+    Organisation,
+}
+
+impl FromStr for WordKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "first-name" => Ok(WordKind::FirstName),
+            "surname" => Ok(WordKind::Surname),
+            "organisation" => Ok(WordKind::Organisation),
+            _ => Err(format!(
+                "unknown word list `{s}`, expected `first-name`, `surname` or `organisation`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WordKind::FirstName => "first-name",
+            WordKind::Surname => "surname",
+            WordKind::Organisation => "organisation",
+        })
+    }
+}
+
+/// The entries of a word list file.
+#[derive(Debug, Clone)]
+pub struct WordList {
+    pub kind: WordKind,
+    /// Locale of the entries, e.g. `en-SG`.
+    pub locale: Option<String>,
+    pub words: Vec<String>,
+}
+
+impl WordList {
+    /// Reads the word list file at `path`.
+    pub fn read(path: &Path, kind: WordKind, locale: Option<String>) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|source| RafError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let words = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect();
+        Ok(WordList {
+            kind,
+            locale,
+            words,
+        })
+    }
+
+    /// Returns `true` if the list has no locale, or one of `locales` or a
+    /// region of one, e.g. `en-SG` for `en`. Every list is in an empty
+    /// `locales`.
+    pub fn in_locales(&self, locales: &[String]) -> bool {
+        match &self.locale {
+            Some(locale) if !locales.is_empty() => locales.iter().any(|wanted| {
+                locale == wanted
+                    || locale
+                        .strip_prefix(wanted.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }),
+            _ => true,
+        }
+    }
+}
+
+/// A [`Detector`] of the entries of word lists.
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    automaton: AhoCorasick,
+    len: usize,
+    /// Whether entries are names, joined into full names.
+    names: bool,
+}
+
+impl Dictionary {
+    /// Finds the first names and surnames of `lists`, or `None` if they
+    /// have none.
+    pub fn names<'a>(lists: impl IntoIterator<Item = &'a WordList>) -> Option<Self> {
+        let words = words(lists, |kind| kind != WordKind::Organisation);
+        Dictionary::new(words, true)
+    }
+
+    /// Finds the organisations of `lists`, or `None` if they have none.
+    pub fn organisations<'a>(lists: impl IntoIterator<Item = &'a WordList>) -> Option<Self> {
+        let words = words(lists, |kind| kind == WordKind::Organisation);
+        Dictionary::new(words, false)
+    }
+
+    fn new(words: Vec<&str>, names: bool) -> Option<Self> {
+        if words.is_empty() {
+            return None;
+        }
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&words)
+            // Only automatons of billions of states fail to build.
+            .expect("the word lists fit in an automaton");
+        Some(Dictionary {
+            automaton,
+            len: words.len(),
+            names,
+        })
+    }
+}
+
+fn words<'a>(
+    lists: impl IntoIterator<Item = &'a WordList>,
+    keep: impl Fn(WordKind) -> bool,
+) -> Vec<&'a str> {
+    lists
+        .into_iter()
+        .filter(|list| keep(list.kind))
+        .flat_map(|list| list.words.iter().map(String::as_str))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+impl Detector for Dictionary {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found: Vec<Range<usize>> = self
+            .automaton
+            .find_overlapping_iter(text)
+            .map(|mat| mat.range())
+            .filter(|range| {
+                lists::is_whole_word(text, range)
+                    && text[range.clone()].starts_with(char::is_uppercase)
+            })
+            .collect();
+        if !self.names {
+            return found;
+        }
+
+        // Join names next to each other, or overlapping, into full names.
+        found.sort_by_key(|range| (range.start, range.end));
+        let mut full_names: Vec<(Range<usize>, usize)> = Vec::new();
+        for range in found {
+            match full_names.last_mut() {
+                Some((full, count))
+                    if range.start <= full.end
+                        || matches!(&text[full.end..range.start], " " | "-") =>
+                {
+                    full.end = full.end.max(range.end);
+                    *count += 1;
+                }
+                _ => full_names.push((range, 1)),
+            }
+        }
+        full_names
+            .into_iter()
+            .filter(|(range, count)| *count > 1 || !starts_sentence(text, range.start))
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        match self.names {
+            true => format!("dictionary of {} name(s)", self.len),
+            false => format!("dictionary of {} organisation(s)", self.len),
+        }
+    }
+}
+
+/// Returns `true` if byte `start` of `text` starts a sentence.
+fn starts_sentence(text: &str, start: usize) -> bool {
+    text[..start]
+        .trim_end()
+        .chars()
+        .next_back()
+        .is_none_or(|c| matches!(c, '.' | '!' | '?'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(kind: WordKind, words: &[&str]) -> WordList {
+        WordList {
+            kind,
+            locale: Some("en-SG".to_owned()),
+            words: words.iter().map(|word| word.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_finds_names_and_organisations() {
+        let lists = [
+            list(WordKind::FirstName, &["May", "Ah", "Kow", "Mark"]),
+            list(WordKind::Surname, &["Tan"]),
+            list(WordKind::Organisation, &["DBS Bank"]),
+        ];
+        let names = Dictionary::names(&lists).unwrap();
+        let text = "May I ask Tan Ah Kow of DBS BANK? Mark the date, mark. Tanjong with May.";
+        let found: Vec<&str> = names.find(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(found, ["Tan Ah Kow", "May"]);
+
+        let organisations = Dictionary::organisations(&lists).unwrap();
+        assert_eq!(organisations.find(text), vec![24..32]);
+        assert!(Dictionary::organisations(&lists[..2]).is_none());
+
+        assert!(lists[0].in_locales(&["en".to_owned()]));
+        assert!(!lists[0].in_locales(&["zh".to_owned(), "e".to_owned()]));
+        assert!(lists[0].in_locales(&[]));
+    }
+}
@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors returned by raf's public API.
+///
+/// The `#[error]` messages below are plain text, with no colour codes:
+/// colouring belongs to the CLI, or whatever else prints them.
+#[derive(Debug, Error)]
+pub enum RafError {
+    /// No registered format can handle the input.
+    #[error("unsupported format `{0}`")]
+    UnsupportedFormat(String),
+
+    /// No registered format detects the file at `path`.
+    #[error(
+        "no registered format can redact {}, supported formats are: {}",
+        path.display(),
+        supported.join(", ")
+    )]
+    UnsupportedFile {
+        path: PathBuf,
+        supported: Vec<&'static str>,
+    },
+
+    /// A format with the same name is already registered.
+    #[error("format `{0}` is already registered")]
+    DuplicateFormat(String),
+
+    /// The `index`-th redaction pattern failed to compile, at `column` of
+    /// the regex if the error is in its syntax.
+    #[error(
+        "invalid pattern {index} `{pattern}`{}: {reason}",
+        column.map_or(String::new(), |column| format!(" at column {column}"))
+    )]
+    PatternInvalid {
+        index: usize,
+        pattern: String,
+        column: Option<usize>,
+        reason: String,
+    },
+
+    /// A type given to select patterns is in no pattern.
+    #[error(
+        "unknown type `{name}`{}",
+        suggestion.as_ref().map_or(String::new(), |s| format!(", did you mean `{s}`?"))
+    )]
+    UnknownType {
+        name: String,
+        suggestion: Option<String>,
+    },
+
+    /// No enabled pattern has any of the types given, so nothing would be
+    /// redacted.
+    #[error("no enabled pattern has any of the types `{}`", .0.join("`, `"))]
+    NoPatterns(Vec<String>),
+
+    /// Several problems with the patterns, all reported at once.
+    #[error(
+        "{} problems with the patterns:\n  {}",
+        .0.len(),
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n  ")
+    )]
+    PatternErrors(Vec<RafError>),
+
+    /// A pattern selects a capture group it does not have.
+    #[error("pattern `{pattern}` has no capture group `{group}`")]
+    PatternGroupMissing { pattern: String, group: String },
+
+    /// Two patterns have the same id.
+    #[error("more than one pattern has the id `{0}`")]
+    PatternDuplicateId(String),
+
+    /// A problem with a pattern of the patterns file `origin`.
+    #[error("{origin}: {source}")]
+    PatternIn {
+        origin: String,
+        #[source]
+        source: Box<RafError>,
+    },
+
+    /// A regex of an allow-list or deny-list file is invalid.
+    #[error("invalid entry on line {line} of {}: {reason}", path.display())]
+    ValueList {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+
+    /// A pattern takes the fake values of a type without a generator.
+    #[error("pattern `{pattern}` uses the generator of `{generator}`, which has none")]
+    UnknownGenerator { pattern: String, generator: String },
+
+    /// A replacement strategy could not be parsed.
+    #[error("{0}")]
+    StrategyInvalid(String),
+
+    /// A keyed strategy is used but no key was given.
+    #[error("the `hmac` and `fpe` strategies need a non-empty secret key")]
+    KeyMissing,
+
+    /// The patterns file is not valid JSON or does not follow the schema.
+    #[error("unable to read patterns from {}, {source}", path.display())]
+    PatternFile {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A value of type `type_name` has too few digits and letters for the
+    /// `fpe` strategy.
+    #[error(
+        "a `{type_name}` value is too short for the `fpe` strategy, which needs 6 digits or 5 letters"
+    )]
+    FpeTooShort { type_name: String },
+
+    /// A token has too few digits and letters to have been written by the
+    /// `fpe` strategy.
+    #[error("the token is too short to have been encrypted by the `fpe` strategy")]
+    FpeTokenInvalid,
+
+    /// Reading or writing `path` failed.
+    #[error("unable to access {}, {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Reading the input document or writing the redacted one failed.
+    #[error("unable to read or write the document, {0}")]
+    Stream(#[source] std::io::Error),
+
+    /// Writing to `path` would overwrite an existing file.
+    #[error("{} already exists and overwriting was not requested", .0.display())]
+    OutputExists(PathBuf),
+
+    /// `path` has no file name to derive the output names from.
+    #[error("{} has no file name", .0.display())]
+    InvalidPath(PathBuf),
+
+    /// A text document is not valid UTF-8.
+    #[error("text is not valid UTF-8, {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+
+    /// A PDF could not be parsed or written.
+    #[error("pdf error, {0}")]
+    Pdf(#[from] lopdf::Error),
+
+    /// A DOCX could not be parsed or written.
+    #[error("docx error, {0}")]
+    Docx(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// The unredact mapping at `path` could not be written.
+    #[error("unable to write the mapping to {}, {source}", path.display())]
+    MappingWrite {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The unredact mapping at `path` could not be read.
+    #[error("unable to read the mapping {}, {source}", path.display())]
+    MappingRead {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The mapping at the path is encrypted but no passphrase or key file
+    /// was given.
+    #[error("{} is encrypted, a passphrase or key file is needed to read it", .0.display())]
+    VaultKeyMissing(PathBuf),
+
+    /// The encrypted mapping could not be decrypted.
+    #[error("unable to decrypt {}, the passphrase or key file is wrong or the file was tampered with", .0.display())]
+    VaultDecrypt(PathBuf),
+
+    /// The encrypted mapping is truncated or of an unknown version.
+    #[error("{} is not a valid encrypted mapping", .0.display())]
+    VaultInvalid(PathBuf),
+
+    /// Mappings were to be both encrypted and appended to a store, which
+    /// is written in clear.
+    #[error("a mapping store is not encrypted and cannot be combined with encrypted mappings")]
+    StoreEncrypted,
+
+    /// A token looked up in a mapping store stands for several values, as
+    /// the tokens of the `label` and `counter` strategies can.
+    #[error("the token `{token}` was redacted from {count} different values, look one up by `--value` instead")]
+    AmbiguousToken { token: String, count: usize },
+
+    /// The format cannot restore documents it redacted.
+    #[error("the `{0}` format does not support unredacting")]
+    UnredactUnsupported(&'static str),
+
+    /// Any other failure, typically raised by a custom format.
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Shorthand for results whose error is a [`RafError`].
+pub type Result<T> = std::result::Result<T, RafError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputPlan;
+    use crate::patterns::{PatternSet, PatternSource};
+    use crate::Redactor;
+    use std::fs;
+
+    #[test]
+    fn test_library_errors() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("raf-errors-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let redactor = Redactor::builder().patterns([r"\d{8}"]).build()?;
+        let mut errors = Vec::new();
+
+        let missing = dir.join("missing.json");
+        let err = PatternSet::new()
+            .add_file(&missing, PatternSource::File(missing.clone()))
+            .unwrap_err();
+        assert!(matches!(&err, RafError::Io { path, .. } if *path == missing));
+        errors.push(err);
+
+        let patterns = dir.join("patterns.json");
+        fs::write(&patterns, "{not json")?;
+        let err = PatternSet::new()
+            .add_file(&patterns, PatternSource::File(patterns.clone()))
+            .unwrap_err();
+        assert!(matches!(&err, RafError::PatternFile { path, .. } if *path == patterns));
+        errors.push(err);
+
+        let odt = dir.join("notes.odt");
+        fs::write(&odt, "call 91234567")?;
+        let err = redactor.scan_file(&odt).unwrap_err();
+        assert!(matches!(&err, RafError::UnsupportedFile { path, supported }
+            if *path == odt && supported.contains(&"txt")));
+        errors.push(err);
+
+        let txt = dir.join("notes.txt");
+        fs::write(&txt, "call 91234567")?;
+        let plan = OutputPlan::sibling(&dir);
+        redactor.redact_file(&txt, &plan)?;
+        let err = redactor.redact_file(&txt, &plan).unwrap_err();
+        let output = dir.join("redacted").join("notes.txt");
+        assert!(matches!(&err, RafError::OutputExists(path) if *path == output));
+        errors.push(err);
+
+        for err in errors {
+            let message = err.to_string();
+            assert!(!message.contains('\u{1b}'), "{message:?}");
+            assert!(message.contains(dir.to_str().unwrap()), "{message}");
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
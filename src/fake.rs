@@ -0,0 +1,284 @@
+//! Generators of fake values for [`Strategy::Fake`](crate::Strategy::Fake).
+//!
+//! A fake value has the same type and shape as the value it replaces, so
+//! redacted documents still pass the validation of the systems that read
+//! them: fake NRICs have a correct checksum letter, fake card numbers pass
+//! the Luhn check, and so on.
+
+use rand::{Rng, RngCore};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Produces a fake value of one type of PII.
+pub trait FakeGenerator: Send + Sync {
+    /// Returns a fake value shaped like `value`, drawing randomness from
+    /// `rng` only, so that the same seed gives the same value.
+    fn generate(&self, value: &str, rng: &mut dyn RngCore) -> String;
+}
+
+/// The [`FakeGenerator`] of each type name.
+///
+/// Types without a generator of their own fall back to [`ShapeGenerator`].
+#[derive(Clone)]
+pub struct GeneratorRegistry {
+    generators: HashMap<String, Arc<dyn FakeGenerator>>,
+}
+
+impl GeneratorRegistry {
+    /// An empty registry, without any of the built-in generators.
+    pub fn new() -> Self {
+        GeneratorRegistry {
+            generators: HashMap::new(),
+        }
+    }
+
+    /// Registers `generator` for the type `type_name`, replacing the one
+    /// already registered for it, if any.
+    pub fn register<G: FakeGenerator + 'static>(
+        &mut self,
+        type_name: impl Into<String>,
+        generator: G,
+    ) -> &mut Self {
+        self.generators
+            .insert(type_name.into(), Arc::new(generator));
+        self
+    }
+
+    /// Returns the generator used for the type `type_name`.
+    pub fn get(&self, type_name: &str) -> &dyn FakeGenerator {
+        self.generators
+            .get(type_name)
+            .map_or(&ShapeGenerator, |generator| generator.as_ref())
+    }
+
+    /// Returns `true` if a generator is registered for the type `type_name`.
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.generators.contains_key(type_name)
+    }
+
+    fn register_all(&mut self, type_names: &[&str], generator: Arc<dyn FakeGenerator>) {
+        for type_name in type_names {
+            self.generators
+                .insert((*type_name).to_owned(), generator.clone());
+        }
+    }
+}
+
+impl Default for GeneratorRegistry {
+    /// A registry with the generators of the types of the bundled
+    /// `patterns.json`: `email`, `nric`, `phone` and `credit_card`.
+    fn default() -> Self {
+        let mut registry = GeneratorRegistry::new();
+        registry.register_all(&["email", "emails"], Arc::new(EmailGenerator));
+        registry.register_all(&["nric"], Arc::new(NricGenerator));
+        registry.register_all(&["phone"], Arc::new(SgPhoneGenerator));
+        registry.register_all(&["credit_card", "card"], Arc::new(CardGenerator));
+        registry
+    }
+}
+
+/// Replaces every digit with a random digit and every ASCII letter with a
+/// random letter of the same case, keeping everything else.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShapeGenerator;
+
+impl FakeGenerator for ShapeGenerator {
+    fn generate(&self, value: &str, rng: &mut dyn RngCore) -> String {
+        value
+            .chars()
+            .map(|c| match c {
+                '0'..='9' => random_digit(rng),
+                'a'..='z' => rng.gen_range(b'a'..=b'z') as char,
+                'A'..='Z' => rng.gen_range(b'A'..=b'Z') as char,
+                _ => c,
+            })
+            .collect()
+    }
+}
+
+/// Emails on the reserved `example.com` domain, with a random local part as
+/// long as the original one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EmailGenerator;
+
+impl FakeGenerator for EmailGenerator {
+    fn generate(&self, value: &str, rng: &mut dyn RngCore) -> String {
+        let local_len = value.find('@').unwrap_or(value.len()).max(1);
+        let local: String = (0..local_len)
+            .map(|_| rng.gen_range(b'a'..=b'z') as char)
+            .collect();
+        format!("{local}@example.com")
+    }
+}
+
+/// Singapore NRIC and FIN numbers with the prefix letter of the original and
+/// a correct checksum letter.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NricGenerator;
+
+impl FakeGenerator for NricGenerator {
+    fn generate(&self, value: &str, rng: &mut dyn RngCore) -> String {
+        let prefix = value
+            .chars()
+            .next()
+            .filter(|c| matches!(c, 'S' | 'T' | 'F' | 'G'))
+            .unwrap_or('S');
+        let digits: Vec<u32> = (0..7).map(|_| rng.gen_range(0..10)).collect();
+        let mut nric = String::with_capacity(9);
+        nric.push(prefix);
+        nric.extend(digits.iter().map(|d| char::from_digit(*d, 10).unwrap()));
+        nric.push(nric_checksum(prefix, &digits));
+        nric
+    }
+}
+
+/// The checksum letter of an NRIC or FIN with the given prefix and digits.
+pub fn nric_checksum(prefix: char, digits: &[u32]) -> char {
+    const WEIGHTS: [u32; 7] = [2, 7, 6, 5, 4, 3, 2];
+    const ST_LETTERS: &[u8] = b"JZIHGFEDCBA";
+    const FG_LETTERS: &[u8] = b"XWUTRQPNMLK";
+    let offset = if matches!(prefix, 'T' | 'G') { 4 } else { 0 };
+    let sum: u32 = offset + digits.iter().zip(WEIGHTS).map(|(d, w)| d * w).sum::<u32>();
+    let letters = if matches!(prefix, 'F' | 'G') {
+        FG_LETTERS
+    } else {
+        ST_LETTERS
+    };
+    letters[(sum % 11) as usize] as char
+}
+
+/// Singapore phone numbers, keeping the `+65` prefix, the separators and
+/// the kind of number (`6` landline, `8`/`9` mobile, `1800` toll-free) of
+/// the original.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SgPhoneGenerator;
+
+impl FakeGenerator for SgPhoneGenerator {
+    fn generate(&self, value: &str, rng: &mut dyn RngCore) -> String {
+        let (country, number) = match value.strip_prefix("+65") {
+            Some(number) => ("+65", number),
+            None => ("", value),
+        };
+        // Keep the leading digits that tell the kind of number.
+        let kept = match number.trim_start().as_bytes() {
+            [b'1', b'8', ..] => 4,
+            [b'6' | b'8' | b'9', ..] => 1,
+            _ => 0,
+        };
+        let mut seen = 0;
+        let number: String = number
+            .chars()
+            .map(|c| {
+                if !c.is_ascii_digit() {
+                    return c;
+                }
+                seen += 1;
+                match seen {
+                    n if n <= kept => c,
+                    1 => [b'6', b'8', b'9'][rng.gen_range(0..3)] as char,
+                    _ => random_digit(rng),
+                }
+            })
+            .collect();
+        format!("{country}{number}")
+    }
+}
+
+/// Card numbers keeping the separators and first digit (the card network) of
+/// the original, with a last digit that passes the Luhn check.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CardGenerator;
+
+impl FakeGenerator for CardGenerator {
+    fn generate(&self, value: &str, rng: &mut dyn RngCore) -> String {
+        let count = value.chars().filter(char::is_ascii_digit).count();
+        let mut digits: Vec<u32> = value
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .take(1)
+            .collect();
+        digits.extend((digits.len()..count.saturating_sub(1)).map(|_| rng.gen_range(0..10)));
+        if count > 0 {
+            digits.push(luhn_check_digit(&digits));
+        }
+
+        let mut digits = digits.into_iter();
+        value
+            .chars()
+            .map(|c| match c {
+                '0'..='9' => char::from_digit(digits.next().unwrap_or(0), 10).unwrap(),
+                _ => c,
+            })
+            .collect()
+    }
+}
+
+/// The digit to append to `digits` so that the number passes the Luhn check.
+pub fn luhn_check_digit(digits: &[u32]) -> u32 {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, d)| match idx % 2 {
+            0 if d * 2 > 9 => d * 2 - 9,
+            0 => d * 2,
+            _ => *d,
+        })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn random_digit(rng: &mut dyn RngCore) -> char {
+    rng.gen_range(b'0'..=b'9') as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn generate(type_name: &str, value: &str) -> String {
+        let mut rng = StdRng::seed_from_u64(7);
+        GeneratorRegistry::default()
+            .get(type_name)
+            .generate(value, &mut rng)
+    }
+
+    #[test]
+    fn test_nric_checksum() {
+        assert_eq!(nric_checksum('S', &[1, 2, 3, 4, 5, 6, 7]), 'D');
+        let fake = generate("nric", "T0123456Z");
+        let digits: Vec<u32> = fake[1..8]
+            .chars()
+            .map(|c| c.to_digit(10).unwrap())
+            .collect();
+        assert!(fake.starts_with('T'));
+        assert_eq!(fake.chars().last(), Some(nric_checksum('T', &digits)));
+    }
+
+    #[test]
+    fn test_card_passes_luhn() {
+        let fake = generate("credit_card", "4111 1111 1111 1111");
+        assert_eq!(fake.len(), 19);
+        assert!(fake.starts_with('4'));
+        let digits: Vec<u32> = fake.chars().filter_map(|c| c.to_digit(10)).collect();
+        assert_eq!(luhn_check_digit(&digits[..15]), digits[15]);
+    }
+
+    #[test]
+    fn test_email_and_phone_keep_their_shape() {
+        let email = generate("email", "john.doe@mail.com");
+        assert!(email.ends_with("@example.com"));
+        assert_eq!(email.find('@'), Some(8));
+
+        let phone = generate("phone", "+65 9123 4567");
+        assert!(phone.starts_with("+65 9"));
+        assert_eq!(phone.len(), 13);
+        assert!(generate("phone", "1800-1234567").starts_with("1800-"));
+
+        let shaped = generate("postcode", "Blk 12A");
+        assert_eq!(shaped.len(), 7);
+        assert!(shaped[4..6].chars().all(|c| c.is_ascii_digit()));
+    }
+}
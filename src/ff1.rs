@@ -0,0 +1,151 @@
+use aes::Aes256;
+use fpe::ff1::{FlexibleNumeralString, FF1};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Tweak of every encryption, so that tokens can be decrypted without
+/// knowing the type of the value they replaced.
+const TWEAK: &[u8] = b"raf";
+
+/// Format-preserving encryption of [`Strategy::Fpe`](crate::Strategy::Fpe).
+///
+/// The ASCII digits of a value are encrypted together with FF1 in radix 10
+/// and its ASCII letters in radix 26, keeping the case of each letter and
+/// every other character where it is. FF1 needs at least 6 digits or 5
+/// letters, so a class with fewer, such as the prefix and checksum letters
+/// of an NRIC, is instead shifted by a keystream derived from the key and
+/// the other class. A value with too few of both cannot be encrypted.
+pub(crate) struct Ff1Cipher {
+    digits: FF1<Aes256>,
+    letters: FF1<Aes256>,
+    key: [u8; 32],
+}
+
+impl Ff1Cipher {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        Ff1Cipher {
+            digits: FF1::new(key, 10).expect("10 is a valid radix"),
+            letters: FF1::new(key, 26).expect("26 is a valid radix"),
+            key: *key,
+        }
+    }
+
+    /// Encrypts `value`, or returns `None` if it is too short to encrypt.
+    pub(crate) fn encrypt(&self, value: &str) -> Option<String> {
+        self.apply(value, true)
+    }
+
+    /// Decrypts a value returned by [`encrypt`](Self::encrypt).
+    pub(crate) fn decrypt(&self, value: &str) -> Option<String> {
+        self.apply(value, false)
+    }
+
+    fn apply(&self, value: &str, encrypt: bool) -> Option<String> {
+        let digits: Vec<u16> = value
+            .bytes()
+            .filter(u8::is_ascii_digit)
+            .map(|b| u16::from(b - b'0'))
+            .collect();
+        let letters: Vec<u16> = value
+            .bytes()
+            .filter(u8::is_ascii_alphabetic)
+            .map(|b| u16::from(b.to_ascii_lowercase() - b'a'))
+            .collect();
+        let crypt = |cipher: &FF1<Aes256>, numerals: &[u16]| {
+            let numerals = FlexibleNumeralString::from(numerals.to_vec());
+            let result = match encrypt {
+                true => cipher.encrypt(TWEAK, &numerals),
+                false => cipher.decrypt(TWEAK, &numerals),
+            };
+            result.ok().map(Vec::<u16>::from)
+        };
+        // The short class is shifted under the plaintext of the long one,
+        // which decryption recovers first.
+        let (new_digits, new_letters) =
+            match (crypt(&self.digits, &digits), crypt(&self.letters, &letters)) {
+                (None, None) => return None,
+                (Some(new_digits), Some(new_letters)) => (new_digits, new_letters),
+                (Some(new_digits), None) => {
+                    let plain = if encrypt { &digits } else { &new_digits };
+                    let new_letters = self.shift(&letters, 26, plain, encrypt);
+                    (new_digits, new_letters)
+                }
+                (None, Some(new_letters)) => {
+                    let plain = if encrypt { &letters } else { &new_letters };
+                    let new_digits = self.shift(&digits, 10, plain, encrypt);
+                    (new_digits, new_letters)
+                }
+            };
+
+        let mut new_digits = new_digits.into_iter();
+        let mut new_letters = new_letters.into_iter();
+        let output = value
+            .chars()
+            .map(|c| match c {
+                '0'..='9' => (b'0' + new_digits.next().unwrap_or(0) as u8) as char,
+                'a'..='z' => (b'a' + new_letters.next().unwrap_or(0) as u8) as char,
+                'A'..='Z' => (b'A' + new_letters.next().unwrap_or(0) as u8) as char,
+                _ => c,
+            })
+            .collect();
+        Some(output)
+    }
+
+    /// Adds to each of `numerals`, or subtracts when decrypting, a keystream
+    /// of the key and the `tweak` numerals, modulo `radix`.
+    fn shift(&self, numerals: &[u16], radix: u16, tweak: &[u16], encrypt: bool) -> Vec<u16> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(TWEAK);
+        mac.update(&[radix as u8]);
+        for numeral in tweak {
+            mac.update(&[*numeral as u8]);
+        }
+        let keystream = mac.finalize().into_bytes();
+        numerals
+            .iter()
+            .zip(keystream.iter().cycle())
+            .map(|(&numeral, &k)| {
+                let k = u16::from(k) % radix;
+                match encrypt {
+                    true => (numeral + k) % radix,
+                    false => (numeral + radix - k) % radix,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_format() {
+        let cipher = Ff1Cipher::new(&[7; 32]);
+        for value in ["S1234567D", "+65 9123 4567", "John.Doe@mail.com"] {
+            let encrypted = cipher.encrypt(value).unwrap();
+            assert_ne!(encrypted, value);
+            assert_eq!(encrypted.len(), value.len());
+            assert_eq!(cipher.decrypt(&encrypted).unwrap(), value);
+        }
+        // The letters of an NRIC are too few for FF1 but still encrypted,
+        // differently for different digits.
+        let nrics: Vec<String> = ["S1234567D", "S7654321D"]
+            .iter()
+            .map(|nric| cipher.encrypt(nric).unwrap())
+            .collect();
+        assert!(nrics
+            .iter()
+            .all(|nric| nric[1..8].bytes().all(|b| b.is_ascii_digit())));
+        let letters: Vec<String> = nrics
+            .iter()
+            .map(|nric| nric.replace(char::is_numeric, ""))
+            .collect();
+        assert!(
+            letters[0] != "SD" && letters[0] != letters[1],
+            "{letters:?}"
+        );
+        assert!(cipher.encrypt("A-12").is_none());
+    }
+}
@@ -0,0 +1,395 @@
+use crate::error::{RafError, Result};
+use crate::mapping::{DocumentPart, RedactedData};
+use crate::unredact::Unredactor;
+use crate::{pdf, redact, Redactor};
+use docx_rs::DocumentChild;
+use lopdf::Document;
+use std::io::Write;
+use std::path::Path;
+
+/// A file format that raf knows how to redact.
+///
+/// Implementors are registered in a [`FormatRegistry`], which picks the
+/// implementation to use for a given file. Text found in the document is
+/// handed to [`Redactor::redact_text`].
+pub trait FormatRedactor: Send + Sync {
+    /// Short, unique name of the format, e.g. `txt`.
+    fn name(&self) -> &'static str;
+
+    /// File extensions (without the leading `.`) handled by this format.
+    fn extensions(&self) -> &[&'static str];
+
+    /// Returns `true` if the file at `path`, whose content starts with `head`,
+    /// is of this format. Defaults to matching on the file extension.
+    fn detect(&self, path: &Path, _head: &[u8]) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| {
+                self.extensions()
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(ext))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Redacts `input` with `redactor`, writes the redacted document to
+    /// `output` and returns every match that was redacted.
+    fn redact(
+        &self,
+        input: &[u8],
+        redactor: &Redactor,
+        output: &mut dyn Write,
+    ) -> Result<Vec<RedactedData>>;
+
+    /// Restores the original text of a document redacted by
+    /// [`redact`](Self::redact), handing its text to
+    /// [`Unredactor::restore_text`] and writing the result to `output`.
+    /// Formats that cannot be restored keep the default, which fails.
+    fn unredact(
+        &self,
+        _input: &[u8],
+        _unredactor: &mut Unredactor,
+        _output: &mut dyn Write,
+    ) -> Result<()> {
+        Err(RafError::UnredactUnsupported(self.name()))
+    }
+}
+
+/// Plain text files.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TxtRedactor;
+
+impl FormatRedactor for TxtRedactor {
+    fn name(&self) -> &'static str {
+        "txt"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["txt"]
+    }
+
+    fn redact(
+        &self,
+        input: &[u8],
+        redactor: &Redactor,
+        output: &mut dyn Write,
+    ) -> Result<Vec<RedactedData>> {
+        let text = std::str::from_utf8(input)?;
+        let (redacted_text, redacted_data) = redactor.redact_text(text)?;
+        output
+            .write_all(redacted_text.as_bytes())
+            .map_err(RafError::Stream)?;
+        Ok(redacted_data)
+    }
+
+    fn unredact(
+        &self,
+        input: &[u8],
+        unredactor: &mut Unredactor,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let text = std::str::from_utf8(input)?;
+        output
+            .write_all(unredactor.restore_text(text).as_bytes())
+            .map_err(RafError::Stream)
+    }
+}
+
+/// Microsoft Word `.docx` documents.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DocxRedactor;
+
+impl FormatRedactor for DocxRedactor {
+    fn name(&self) -> &'static str {
+        "docx"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["docx"]
+    }
+
+    fn redact(
+        &self,
+        input: &[u8],
+        redactor: &Redactor,
+        output: &mut dyn Write,
+    ) -> Result<Vec<RedactedData>> {
+        let mut all_redacted_data: Vec<RedactedData> = Vec::new();
+        rewrite_docx(input, output, &mut |text, part| {
+            let (redacted_text, redacted_data) = redactor.redact_part(text, part)?;
+            all_redacted_data.extend(redacted_data);
+            Ok(redacted_text)
+        })?;
+        Ok(all_redacted_data)
+    }
+
+    fn unredact(
+        &self,
+        input: &[u8],
+        unredactor: &mut Unredactor,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        rewrite_docx(input, output, &mut |text, part| {
+            Ok(unredactor.restore_part(text, part))
+        })
+    }
+}
+
+/// Replaces the text of every paragraph of a `.docx` with the result of `f`.
+fn rewrite_docx(
+    input: &[u8],
+    output: &mut dyn Write,
+    f: &mut dyn FnMut(&str, DocumentPart) -> Result<String>,
+) -> Result<()> {
+    let mut docx = docx_rs::read_docx(input).map_err(|err| RafError::Docx(Box::new(err)))?;
+    let paragraphs = docx
+        .document
+        .children
+        .iter_mut()
+        .filter_map(|child| match child {
+            DocumentChild::Paragraph(para) => Some(para),
+            _ => None,
+        });
+    for (idx, para) in paragraphs.enumerate() {
+        redact::map_paragraph_text(para, idx, f)?;
+    }
+
+    // `pack` needs `Seek`, which a plain writer may not offer.
+    let mut buf = std::io::Cursor::new(Vec::new());
+    docx.build()
+        .pack(&mut buf)
+        .map_err(|err| RafError::Docx(Box::new(err)))?;
+    output.write_all(buf.get_ref()).map_err(RafError::Stream)
+}
+
+/// PDF documents. Only text drawn with the `Tj` and `TJ` operators is redacted.
+///
+/// Not part of [`FormatRegistry::default`] yet, as the output of some PDFs is
+/// still mangled; register it explicitly to opt in.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PdfRedactor;
+
+impl FormatRedactor for PdfRedactor {
+    fn name(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["pdf"]
+    }
+
+    fn redact(
+        &self,
+        input: &[u8],
+        redactor: &Redactor,
+        mut output: &mut dyn Write,
+    ) -> Result<Vec<RedactedData>> {
+        let mut all_redacted_data: Vec<RedactedData> = Vec::new();
+        let mut pdf = Document::load_mem(input)?;
+        pdf::replace_text(&mut pdf, &mut |text, part| {
+            let (redacted_text, redacted_data) = redactor.redact_part(text, part)?;
+            all_redacted_data.extend(redacted_data);
+            Ok(redacted_text)
+        })?;
+        pdf.save_to(&mut output).map_err(RafError::Stream)?;
+        Ok(all_redacted_data)
+    }
+
+    fn unredact(
+        &self,
+        input: &[u8],
+        unredactor: &mut Unredactor,
+        mut output: &mut dyn Write,
+    ) -> Result<()> {
+        let mut pdf = Document::load_mem(input)?;
+        pdf::replace_text(&mut pdf, &mut |text, part| {
+            Ok(unredactor.restore_part(text, part))
+        })?;
+        pdf.save_to(&mut output).map_err(RafError::Stream)
+    }
+}
+
+/// Maps file formats to their [`FormatRedactor`] implementations.
+///
+/// Formats are looked up in registration order, so a format registered later
+/// never shadows an earlier one for the same file.
+pub struct FormatRegistry {
+    redactors: Vec<Box<dyn FormatRedactor>>,
+}
+
+impl FormatRegistry {
+    /// An empty registry, without any of the built-in formats.
+    pub fn new() -> Self {
+        FormatRegistry {
+            redactors: Vec::new(),
+        }
+    }
+
+    /// Registers `redactor`, returning an error if a format with the same
+    /// name is already registered.
+    pub fn register<R: FormatRedactor + 'static>(&mut self, redactor: R) -> Result<&mut Self> {
+        self.register_boxed(Box::new(redactor))
+    }
+
+    /// Same as [`register`](Self::register), for an already boxed format.
+    pub fn register_boxed(&mut self, redactor: Box<dyn FormatRedactor>) -> Result<&mut Self> {
+        if self.get(redactor.name()).is_some() {
+            return Err(RafError::DuplicateFormat(redactor.name().to_owned()));
+        }
+        self.redactors.push(redactor);
+        Ok(self)
+    }
+
+    /// Returns the format registered under `name`.
+    pub fn get(&self, name: &str) -> Option<&dyn FormatRedactor> {
+        self.redactors
+            .iter()
+            .find(|r| r.name() == name)
+            .map(|r| r.as_ref())
+    }
+
+    /// Returns the first registered format that detects the file at `path`.
+    pub fn detect(&self, path: &Path, head: &[u8]) -> Option<&dyn FormatRedactor> {
+        self.redactors
+            .iter()
+            .find(|r| r.detect(path, head))
+            .map(|r| r.as_ref())
+    }
+
+    /// Names of all registered formats, in registration order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.redactors.iter().map(|r| r.name()).collect()
+    }
+}
+
+impl Default for FormatRegistry {
+    /// A registry with the built-in `txt` and `docx` formats.
+    fn default() -> Self {
+        FormatRegistry {
+            redactors: vec![Box::new(TxtRedactor), Box::new(DocxRedactor)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CsvRedactor;
+
+    impl FormatRedactor for CsvRedactor {
+        fn name(&self) -> &'static str {
+            "csv"
+        }
+
+        fn extensions(&self) -> &[&'static str] {
+            &["csv"]
+        }
+
+        fn redact(
+            &self,
+            input: &[u8],
+            redactor: &Redactor,
+            output: &mut dyn Write,
+        ) -> Result<Vec<RedactedData>> {
+            TxtRedactor.redact(input, redactor, output)
+        }
+    }
+
+    #[test]
+    fn test_registry_detects_by_extension() {
+        let registry = FormatRegistry::default();
+        let found = registry.detect(Path::new("a/b/Report.DOCX"), b"");
+        assert_eq!(found.map(|r| r.name()), Some("docx"));
+        assert!(registry.detect(Path::new("a/b/noextension"), b"").is_none());
+    }
+
+    /// A one-page PDF drawing `strings` with a single `TJ` operator.
+    fn pdf_with_tj(strings: &[&str]) -> Vec<u8> {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{dictionary, Object, Stream};
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let operands = strings.iter().map(|s| Object::string_literal(*s)).collect();
+        let content = Content {
+            operations: vec![Operation::new("TJ", vec![Object::Array(operands)])],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        let mut pdf = Vec::new();
+        doc.save_to(&mut pdf).unwrap();
+        pdf
+    }
+
+    #[test]
+    fn test_pdf_strings_are_parts_of_their_own() -> Result<()> {
+        // Both strings of the `TJ` array hold a match at the same offset.
+        let input = pdf_with_tj(&["tel 91234567", "fax 81234567"]);
+        let redactor = Redactor::builder()
+            .patterns([r"\d{8}"])
+            .strategy(crate::Strategy::Label(None))
+            .build()?;
+        let mut redacted = Vec::new();
+        let mapping = PdfRedactor.redact(&input, &redactor, &mut redacted)?;
+        let parts: Vec<_> = mapping
+            .iter()
+            .map(|data| data.location.as_ref().and_then(|l| l.part.clone()))
+            .collect();
+        assert_eq!(
+            parts,
+            [0, 1].map(|operand| Some(DocumentPart::Pdf {
+                page: 1,
+                operator: 0,
+                operand,
+            }))
+        );
+
+        // Restore in the reverse order, so only the parts tell the two equal
+        // label tokens apart.
+        let mut unredactor = Unredactor::new(mapping.into_iter().rev().collect());
+        let mut restored = Vec::new();
+        PdfRedactor.unredact(&redacted, &mut unredactor, &mut restored)?;
+        assert!(unredactor.finish().is_complete());
+        let restored = Document::load_mem(&restored)?;
+        let text = restored.get_page_content(restored.page_iter().next().unwrap())?;
+        let text = String::from_utf8_lossy(&text);
+        assert!(text.contains("(tel 91234567)(fax 81234567)"), "{text}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_custom_format() -> Result<()> {
+        let mut registry = FormatRegistry::default();
+        registry.register(PdfRedactor)?.register(CsvRedactor)?;
+        assert_eq!(registry.names(), vec!["txt", "docx", "pdf", "csv"]);
+        assert!(registry.register(CsvRedactor).is_err());
+
+        let redactor = Redactor::builder().patterns([r"\d{8}"]).build()?;
+        let mut output = Vec::new();
+        let redacted_data = registry
+            .detect(Path::new("contacts.csv"), b"")
+            .expect("csv is registered")
+            .redact(b"alice,91234567", &redactor, &mut output)?;
+        assert_eq!(redacted_data.len(), 1);
+        assert!(!String::from_utf8(output).unwrap().contains("91234567"));
+        Ok(())
+    }
+}
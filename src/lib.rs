@@ -0,0 +1,51 @@
+//! raf redacts text matching a set of regexes in `.txt` and `.docx` files.
+//!
+//! The [`Redactor`] is the entry point; it redacts plain text, in-memory
+//! documents and files on disk, and records what was replaced so that the
+//! redaction can be reversed with an [`Unredactor`].
+//!
+//! ```no_run
+//! let redactor = raf::Redactor::builder()
+//!     .patterns([r"[STFG]\d{7}[A-Z]"])
+//!     .build()?;
+//! let (redacted, mapping) = redactor.redact_text("NRIC: S1234567D")?;
+//! # Ok::<(), raf::RafError>(())
+//! ```
+//!
+//! Additional document formats can be plugged in by implementing
+//! [`format::FormatRedactor`] and registering it with
+//! [`RedactorBuilder::format`].
+
+pub mod dictionary;
+mod error;
+pub mod fake;
+mod ff1;
+pub mod format;
+pub mod lists;
+pub mod mapping;
+pub mod matcher;
+pub mod output;
+pub mod patterns;
+mod pdf;
+mod redact;
+mod redactor;
+pub mod report;
+pub mod review;
+mod sarif;
+pub mod store;
+mod strategy;
+pub mod unredact;
+mod utils;
+pub mod validate;
+pub mod vault;
+
+pub use crate::error::{RafError, Result};
+pub use crate::mapping::{read_mapping, write_mapping, RedactedData};
+pub use crate::matcher::{
+    CaptureGroup, CompiledPattern, ContextKeywords, Detector, Finder, OverlapPolicy,
+};
+pub use crate::redactor::{Redactor, RedactorBuilder};
+pub use crate::strategy::Strategy;
+pub use crate::unredact::{UnredactReport, Unredactor};
+pub use crate::utils::{get_files_dirs_from_folder, get_pattern_vec};
+pub use crate::validate::Validator;
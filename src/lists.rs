@@ -0,0 +1,196 @@
+//! Allow-lists of values never to redact and deny-lists of values always to
+//! redact, e.g. the company switchboard number and known customer names.
+//!
+//! A list file holds one entry per line: a literal value, or a regex after
+//! `re:`. Blank lines and lines starting with `#` are skipped. Literals
+//! match regardless of ASCII case, and deny-list literals only as whole
+//! words.
+
+use crate::error::{RafError, Result};
+use crate::matcher::Detector;
+use aho_corasick::AhoCorasick;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// The entries of a list file.
+#[derive(Debug, Clone, Default)]
+pub struct ValueList {
+    pub literals: Vec<String>,
+    pub regexes: Vec<Regex>,
+}
+
+impl ValueList {
+    /// Reads the list file at `path`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|source| RafError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut list = ValueList::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix("re:") {
+                Some(regex) => {
+                    let regex = Regex::new(regex).map_err(|error| RafError::ValueList {
+                        path: path.to_path_buf(),
+                        line: idx + 1,
+                        reason: error.to_string(),
+                    })?;
+                    list.regexes.push(regex);
+                }
+                None => list.literals.push(line.to_owned()),
+            }
+        }
+        Ok(list)
+    }
+
+    /// Adds the entries of `other`.
+    pub fn extend(&mut self, other: ValueList) {
+        self.literals.extend(other.literals);
+        self.regexes.extend(other.regexes);
+    }
+}
+
+/// Values never redacted, by the patterns of one type or of all types.
+#[derive(Debug, Clone)]
+pub struct AllowList {
+    type_name: Option<String>,
+    literals: HashSet<String>,
+    /// The regexes of the list, anchored to match whole values.
+    regexes: Vec<Regex>,
+}
+
+impl AllowList {
+    /// Allows the values of `list` for the patterns of type `type_name`, or
+    /// of all types if `None`.
+    pub fn new(list: ValueList, type_name: Option<String>) -> Self {
+        let regexes = list
+            .regexes
+            .iter()
+            .map(|regex| {
+                Regex::new(&format!(r"\A(?:{})\z", regex.as_str()))
+                    .expect("a valid regex stays valid in a group")
+            })
+            .collect();
+        AllowList {
+            type_name,
+            literals: list
+                .literals
+                .iter()
+                .map(|value| value.to_ascii_lowercase())
+                .collect(),
+            regexes,
+        }
+    }
+
+    /// Returns `true` if `value`, matched by a pattern of `types`, is
+    /// allowed.
+    pub fn allows(&self, types: &[String], value: &str) -> bool {
+        let applies = self
+            .type_name
+            .as_ref()
+            .is_none_or(|type_name| types.contains(type_name));
+        applies
+            && (self.literals.contains(&value.to_ascii_lowercase())
+                || self.regexes.iter().any(|regex| regex.is_match(value)))
+    }
+}
+
+/// Values always redacted, found with a single Aho-Corasick automaton for
+/// the literals so that lists of thousands of names stay fast.
+///
+/// A deny-list is a [`Detector`]; see
+/// [`RedactorBuilder::deny_list`](crate::RedactorBuilder::deny_list).
+#[derive(Debug, Clone)]
+pub struct DenyList {
+    literals: AhoCorasick,
+    regexes: Vec<Regex>,
+    len: usize,
+}
+
+impl DenyList {
+    pub fn new(list: ValueList) -> Self {
+        let literals = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&list.literals)
+            // Only automatons of billions of states fail to build.
+            .expect("the deny-list fits in an automaton");
+        DenyList {
+            literals,
+            len: list.literals.len() + list.regexes.len(),
+            regexes: list.regexes,
+        }
+    }
+}
+
+impl Detector for DenyList {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found: Vec<Range<usize>> = self
+            .literals
+            .find_overlapping_iter(text)
+            .map(|mat| mat.range())
+            .filter(|range| is_whole_word(text, range))
+            .collect();
+        for regex in &self.regexes {
+            found.extend(regex.find_iter(text).map(|mat| mat.range()));
+        }
+        found
+    }
+
+    fn describe(&self) -> String {
+        format!("deny-list of {} value(s)", self.len)
+    }
+}
+
+/// Returns `true` if `range` of `text` is neither preceded nor followed by
+/// a letter or digit.
+pub(crate) fn is_whole_word(text: &str, range: &Range<usize>) -> bool {
+    let is_word = |c: char| c.is_alphanumeric();
+    !text[..range.start].chars().next_back().is_some_and(is_word)
+        && !text[range.end..].chars().next().is_some_and(is_word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Redactor, Strategy};
+
+    #[test]
+    fn test_allow_and_deny_lists() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("raf-list-{}.txt", std::process::id()));
+        fs::write(&path, "# switchboard\n\n6123 4567\nre:support@.*\n")?;
+        let allowed = ValueList::read(&path)?;
+        fs::write(&path, "re:[a-z")?;
+        assert!(matches!(
+            ValueList::read(&path),
+            Err(RafError::ValueList { line: 1, .. })
+        ));
+        fs::remove_file(&path)?;
+
+        let denied = ValueList {
+            literals: vec!["Tan Ah Kow".to_owned(), "Tan".to_owned()],
+            regexes: Vec::new(),
+        };
+        let redactor = Redactor::builder()
+            .patterns([r"\d{4} \d{4}", r"\S+@\S+"])
+            .allow_list(AllowList::new(allowed, None))
+            .deny_list("name", DenyList::new(denied))
+            .strategy(Strategy::Label(None))
+            .build()?;
+        let (redacted, mapping) = redactor.redact_text(
+            "Call 6123 4567 or 9123 4567, mail support@ourco.com or TAN AH KOW, not Tanjong",
+        )?;
+        assert_eq!(
+            redacted,
+            "Call 6123 4567 or [REDACTED], mail support@ourco.com or [NAME], not Tanjong"
+        );
+        assert_eq!(mapping[1].pattern_id.as_deref(), Some("deny-list:name"));
+        Ok(())
+    }
+}
@@ -1,26 +1,42 @@
 mod args;
-mod pdf;
-mod redact;
-mod utils;
 
 use crate::args::*;
-use anyhow::{Ok, anyhow};
+use anyhow::Ok;
 use clap::Parser;
 use lazy_static::lazy_static;
+use raf::dictionary::{WordKind, WordList};
+use raf::lists::{AllowList, DenyList, ValueList};
+use raf::output::OutputPlan;
+use raf::patterns::PatternSet;
+use raf::report::ScanReport;
+use raf::review::ReviewReport;
+use raf::store::{MappingIndex, MappingStore};
+use raf::vault::{MappingVault, VaultSecret};
+use raf::{CompiledPattern, RedactedData, Redactor, RedactorBuilder, Strategy};
 use rayon::prelude::*;
-use regex::Regex;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
 use text_colorizer::{ColoredString, Colorize};
 
 lazy_static! {
     static ref RED_ERROR_STRING: ColoredString = "ERROR: ".red().bold();
+    static ref WARNING_STRING: ColoredString = "WARNING: ".yellow().bold();
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}{err}", *RED_ERROR_STRING);
+        process::exit(1);
+    }
+}
+
+fn run() -> anyhow::Result<()> {
     let cmd = Opts::parse().cmd;
-    let mut queue = VecDeque::<PathBuf>::new();
 
     match cmd {
         FileOrFolder::Folder(opts) => {
@@ -29,55 +45,519 @@ fn main() -> anyhow::Result<()> {
                 opts.path, opts.recursive, opts.types
             );
 
-            let regex_vec: Vec<Regex> = utils::get_pattern_vec("patterns.json", opts.types)?;
-            queue.push_back(opts.path);
-
-            while let Some(path) = queue.pop_front() {
-                let output_folder = path.join("redacted");
-
-                if !output_folder.exists() {
-                    fs::create_dir(&output_folder).map_err(|err| {
-                        anyhow!(
-                            "{}Failed to create output folder, {err}.",
-                            *RED_ERROR_STRING
-                        )
-                    })?;
-                };
-
-                let (files, dirs, _) = utils::get_files_dirs_from_folder(&path)?;
-                if opts.recursive {
-                    let dirs = dirs.into_iter().filter(|dir| {
-                        // filter out dir name == `redacted`
-                        matches!(dir.file_name().unwrap_or_default().to_str(), Some("redacted"))
-                    });
-                    queue.extend(dirs);
-                }
-
-                let results: Vec<anyhow::Result<()>> = files
-                    .par_iter()
-                    .map(|path| redact::redact_one_file(path, &regex_vec, &output_folder))
-                    .collect::<Vec<anyhow::Result<()>>>(); // end of for_each
+            let redactor = build_redactor(
+                opts.types,
+                &opts.patterns,
+                &opts.lists,
+                &opts.redact,
+                &opts.review,
+            )?;
+            let plan = output_plan(opts.output, opts.path.clone())?;
+            let mut review = review_report(&opts.review);
+            walk_folder(
+                opts.path,
+                opts.recursive,
+                |dir| plan.is_output_dir(dir),
+                |dir, files, errors| {
+                    for err in errors {
+                        eprintln!("{}{err}", *RED_ERROR_STRING);
+                        if let Some(review) = &mut review {
+                            review.add(dir, &Err(err));
+                        }
+                    }
+                    let results: Vec<raf::Result<Vec<RedactedData>>> = files
+                        .par_iter()
+                        .map(|path| redactor.redact_file(path, &plan))
+                        .collect(); // end of for_each
 
-                println!("Processed results: {:?}", results);
-                println!("A folder named `redacted` at {} has been created containing all the redacted files in {}",
-                        output_folder.display(),
-                        path.display());
+                    for (path, result) in files.iter().zip(results) {
+                        if let Some(review) = &mut review {
+                            review.add(path, &result);
+                        }
+                        match result {
+                            std::result::Result::Ok(_) => println!("Redacted {}", path.display()),
+                            Err(err) => eprintln!("{}{err}", *RED_ERROR_STRING),
+                        }
+                    }
+                },
+            )?;
+            save_review(review, &opts.review)
+        }
+        FileOrFolder::File(opts) if opts.path == Path::new("-") => {
+            // stdout carries the redacted document, so report on stderr only.
+            let ignored = [
+                ("--output", opts.output.output.is_some()),
+                ("--in-place", opts.output.in_place),
+                ("--mapping-dir", opts.output.mapping_dir.is_some()),
+            ];
+            if let Some((flag, _)) = ignored.iter().find(|(_, given)| *given) {
+                anyhow::bail!(
+                    "`{flag}` cannot be used when reading from stdin, the redacted file is written to stdout and the mapping to `--mapping`"
+                );
             }
-            Ok(())
+            let redactor = build_redactor(
+                opts.types,
+                &opts.patterns,
+                &opts.lists,
+                &opts.redact,
+                &opts.review,
+            )?;
+            let plan = output_plan(opts.output, PathBuf::new())?;
+            if let Some(mapping) = &opts.mapping {
+                plan.prepare_mapping(mapping)?;
+            }
+            let redacted_data =
+                redactor.redact_reader(io::stdin().lock(), &opts.format, io::stdout().lock())?;
+            match opts.mapping {
+                Some(mapping) => plan.write_mapping(Path::new("-"), &redacted_data, &mapping)?,
+                // The store needs no `--mapping`.
+                None if plan.store.is_some() => {
+                    plan.write_mapping(Path::new("-"), &redacted_data, Path::new(""))?
+                }
+                None if !redacted_data.is_empty() => eprintln!(
+                    "{}No `--mapping` given, {} redaction(s) cannot be reversed.",
+                    *WARNING_STRING,
+                    redacted_data.len()
+                ),
+                None => {}
+            }
+            let mut review = review_report(&opts.review);
+            if let Some(review) = &mut review {
+                review.add(Path::new("-"), &std::result::Result::Ok(redacted_data));
+            }
+            save_review(review, &opts.review)
         }
         FileOrFolder::File(opts) => {
             println!(
                 "File command executed with path {:?} and types {:?}",
                 opts.path, opts.types
             );
-            let output_folder = opts.path.parent().unwrap_or(&opts.path).join("redacted");
-            let regex_vec: Vec<Regex> = utils::get_pattern_vec("patterns.json", opts.types)?;
+            let redactor = build_redactor(
+                opts.types,
+                &opts.patterns,
+                &opts.lists,
+                &opts.redact,
+                &opts.review,
+            )?;
+            let input_root = opts.path.parent().unwrap_or(&opts.path).to_path_buf();
+            let plan = output_plan(opts.output, input_root)?;
 
-            if !output_folder.exists() {
-                fs::create_dir(&output_folder).expect("Failed to create output folder.");
+            let result = redactor.redact_file(&opts.path, &plan);
+            let mut review = review_report(&opts.review);
+            if let Some(review) = &mut review {
+                review.add(&opts.path, &result);
+            }
+            save_review(review, &opts.review)?;
+            result?;
+            Ok(())
+        }
+        FileOrFolder::Scan(opts) => {
+            let patterns = compile_patterns(&opts.types, &opts.patterns, &opts.lists)?;
+            // Only the matches matter, so skip the patterns' own strategies,
+            // some of which need a key.
+            let patterns = patterns.into_iter().map(|pattern| CompiledPattern {
+                strategy: None,
+                ..pattern
+            });
+            let redactor = add_value_lists(Redactor::builder(), &opts.lists)?
+                .compiled_patterns(patterns)
+                .overlap_policy(opts.overlap)
+                .min_score(opts.min_score)
+                .strategy(Strategy::Remove)
+                .build()?;
+
+            let mut report = ScanReport::with_patterns(redactor.matcher());
+            let scan = |report: &mut ScanReport, files: Vec<PathBuf>| {
+                let results: Vec<_> = files
+                    .par_iter()
+                    .map(|path| redactor.scan_file(path))
+                    .collect();
+                for (path, result) in files.iter().zip(results) {
+                    report.add(path, result);
+                }
+            };
+            if opts.path.is_dir() {
+                // Skip the `redacted` folders of earlier `folder` runs, as
+                // `folder` itself does, so they are not counted twice.
+                let plan = OutputPlan::sibling(opts.path.clone());
+                walk_folder(
+                    opts.path,
+                    opts.recursive,
+                    |dir| plan.is_output_dir(dir),
+                    |dir, files, errors| {
+                        for err in errors {
+                            report.add_failure(dir, &err);
+                        }
+                        scan(&mut report, files);
+                    },
+                )?;
+            } else {
+                scan(&mut report, vec![opts.path]);
+            }
+
+            match &opts.output {
+                Some(path) => {
+                    let mut file =
+                        io::BufWriter::new(fs::File::create(path).map_err(|source| {
+                            raf::RafError::Io {
+                                path: path.clone(),
+                                source,
+                            }
+                        })?);
+                    report.write(opts.report_format, opts.samples, &mut file)?;
+                    file.flush()?;
+                }
+                None => report.write(opts.report_format, opts.samples, &mut io::stdout().lock())?,
+            }
+            if opts.fail_on_findings && report.total() > 0 {
+                anyhow::bail!("found {} value(s) to redact", report.total());
+            }
+            Ok(())
+        }
+        FileOrFolder::Unredact(opts) => {
+            let output = match opts.output {
+                Some(output) => output,
+                None => raf::unredact::output_path_for(&opts.path)?,
+            };
+            if output.exists() && !opts.force {
+                return Err(raf::RafError::OutputExists(output).into());
+            }
+
+            let formats = raf::format::FormatRegistry::default();
+            let report = match &opts.mapping_store {
+                Some(store) => {
+                    let index = MappingIndex::load(store)?;
+                    let source = match opts.source {
+                        Some(source) => source,
+                        None => stored_source(&index, store, &opts.path)?,
+                    };
+                    let mapping = index.mapping_of(&source);
+                    if mapping.is_empty() {
+                        anyhow::bail!("{} has no mapping of {}", store.display(), source.display());
+                    }
+                    raf::unredact::restore_file(&opts.path, mapping, &output, &formats)?
+                }
+                None => {
+                    let mapping = match opts.mapping {
+                        Some(mapping) => mapping,
+                        None => raf::unredact::mapping_path_for(&opts.path)?,
+                    };
+                    let secret = read_vault_secret(&opts.vault)?;
+                    raf::unredact::unredact_file(
+                        &opts.path,
+                        &mapping,
+                        secret.as_ref(),
+                        &output,
+                        &formats,
+                    )?
+                }
+            };
+            println!(
+                "Restored {} value(s) into {}",
+                report.restored,
+                output.display()
+            );
+            // Only print tokens, the original values stay in the restored file.
+            for missing in &report.missing {
+                eprintln!(
+                    "{}Token `{}` was not found where expected, it was altered or lost.",
+                    *WARNING_STRING, missing.redacted_text
+                );
+            }
+            if !report.is_complete() {
+                anyhow::bail!(
+                    "{} of {} value(s) could not be restored",
+                    report.missing.len(),
+                    report.missing.len() + report.restored
+                );
+            }
+            Ok(())
+        }
+        FileOrFolder::Decrypt(opts) => {
+            let Some(key) = read_key(&opts.key)? else {
+                return Err(raf::RafError::KeyMissing.into());
+            };
+            let redactor = Redactor::builder().key(key).build()?;
+            let mut stdout = io::stdout().lock();
+            for token in &opts.tokens {
+                writeln!(stdout, "{}", redactor.decrypt(token)?)?;
+            }
+            Ok(())
+        }
+        FileOrFolder::Mapping(MappingCmd::Show(opts)) => {
+            let secret = read_vault_secret(&opts.vault)?;
+            let mapping = raf::vault::read_mapping(&opts.path, secret.as_ref())?;
+            let mut stdout = io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &mapping)?;
+            writeln!(stdout)?;
+            Ok(())
+        }
+        FileOrFolder::Mapping(MappingCmd::Lookup(opts)) => {
+            let index = MappingIndex::load(&opts.path)?;
+            let value = match (&opts.token, &opts.value) {
+                (Some(token), _) => index.value_of_token(token)?,
+                (None, Some(value)) => index.value(value),
+                (None, None) => None,
+            };
+            let Some(value) = value else {
+                anyhow::bail!("nothing found in {}", opts.path.display());
             };
-            redact::redact_one_file(&opts.path, &regex_vec, &output_folder)?;
+            let occurrences: Vec<_> = index.occurrences(value.id).collect();
+            let mut stdout = io::stdout().lock();
+            serde_json::to_writer_pretty(
+                &mut stdout,
+                &serde_json::json!({ "value": value, "occurrences": occurrences }),
+            )?;
+            writeln!(stdout)?;
             Ok(())
         }
+        FileOrFolder::Patterns(PatternsCmd::List(opts)) => {
+            let patterns = PatternSet::load(&opts.files)?;
+            let rows: Vec<[String; 4]> = patterns
+                .entries()
+                .iter()
+                .map(|entry| {
+                    let pattern = &entry.pattern;
+                    let mut id = pattern
+                        .id
+                        .clone()
+                        .unwrap_or_else(|| entry.index.to_string());
+                    if !pattern.enabled {
+                        id.push_str(" (disabled)");
+                    }
+                    [
+                        id,
+                        pattern.types.join(", "),
+                        entry.source.to_string(),
+                        pattern.description.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            let header = ["ID", "TYPES", "SOURCE", "DESCRIPTION"].map(String::from);
+            let mut widths = [0; 4];
+            for row in std::iter::once(&header).chain(&rows) {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            let mut stdout = io::stdout().lock();
+            for row in std::iter::once(&header).chain(&rows) {
+                writeln!(
+                    stdout,
+                    "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2]
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Visits the files under `root` folder by folder, calling `visit` with the
+/// folder, its files and the entries that could not be read. Descends into
+/// the sub-folders `skip` does not reject if `recursive`.
+fn walk_folder(
+    root: PathBuf,
+    recursive: bool,
+    skip: impl Fn(&Path) -> bool,
+    mut visit: impl FnMut(&Path, Vec<PathBuf>, Vec<raf::RafError>),
+) -> anyhow::Result<()> {
+    let mut queue = VecDeque::from([root]);
+    while let Some(path) = queue.pop_front() {
+        let (files, dirs, errors) = raf::get_files_dirs_from_folder(&path)?;
+        if recursive {
+            // never descend into the folders raf writes to
+            queue.extend(dirs.into_iter().filter(|dir| !skip(dir)));
+        }
+        visit(&path, files, errors);
+    }
+    Ok(())
+}
+
+/// The file recorded in the mapping store at `store` with the same name as
+/// the redacted file at `path`, if there is only one.
+fn stored_source(index: &MappingIndex, store: &Path, path: &Path) -> anyhow::Result<PathBuf> {
+    let sources: Vec<&Path> = index
+        .files()
+        .into_iter()
+        .filter(|file| file.file_name() == path.file_name())
+        .collect();
+    match sources[..] {
+        [source] => Ok(source.to_path_buf()),
+        [] => anyhow::bail!(
+            "{} has no file named like {}",
+            store.display(),
+            path.display()
+        ),
+        _ => anyhow::bail!(
+            "{} has {} files named like {}, pick one with `--source`",
+            store.display(),
+            sources.len(),
+            path.display()
+        ),
+    }
+}
+
+/// The output plan of `opts`, with a vault when `--encrypt-mapping` is given.
+fn output_plan(opts: OutputOpts, input_root: PathBuf) -> anyhow::Result<OutputPlan> {
+    let vault = match opts.encrypt_mapping {
+        true => match read_vault_secret(&opts.vault)? {
+            Some(secret) => Some(MappingVault::new(&secret)),
+            None => anyhow::bail!(
+                "`--encrypt-mapping` needs `--vault-key-file` or a passphrase in `{}`",
+                opts.vault.vault_passphrase_env
+            ),
+        },
+        false => None,
+    };
+    let store = match &opts.mapping_store {
+        Some(path) => Some(Arc::new(MappingStore::open(path)?)),
+        None => None,
+    };
+    Ok(OutputPlan {
+        vault,
+        store,
+        ..opts.plan(input_root)
+    })
+}
+
+/// Reads the key file of `--vault-key-file`, else the passphrase in the
+/// `--vault-passphrase-env` variable.
+fn read_vault_secret(opts: &VaultOpts) -> anyhow::Result<Option<VaultSecret>> {
+    match &opts.vault_key_file {
+        Some(path) => Ok(Some(VaultSecret::KeyFile(read_secret_file(path)?))),
+        None => Ok(env::var(&opts.vault_passphrase_env)
+            .ok()
+            .map(VaultSecret::Passphrase)),
+    }
+}
+
+/// An empty review report if `--html-report` is given.
+fn review_report(opts: &ReviewOpts) -> Option<ReviewReport> {
+    opts.html_report
+        .as_ref()
+        .map(|_| ReviewReport::new(opts.mask_originals))
+}
+
+/// Writes `review` to the `--html-report` file, if any.
+fn save_review(review: Option<ReviewReport>, opts: &ReviewOpts) -> anyhow::Result<()> {
+    if let (Some(review), Some(path)) = (review, &opts.html_report) {
+        review.save(path)?;
+        eprintln!("Review report written to {}", path.display());
+    }
+    Ok(())
+}
+
+fn build_redactor(
+    types: Vec<String>,
+    patterns: &PatternOpts,
+    lists: &ListOpts,
+    opts: &RedactOpts,
+    review: &ReviewOpts,
+) -> anyhow::Result<Redactor> {
+    let patterns = compile_patterns(&types, patterns, lists)?;
+    let mut builder = add_value_lists(Redactor::builder(), lists)?
+        .compiled_patterns(patterns)
+        .overlap_policy(opts.overlap)
+        .min_score(opts.min_score);
+    if review.html_report.is_some() {
+        builder = builder.context(raf::review::DEFAULT_CONTEXT);
+    }
+    for arg in &opts.strategy {
+        builder = match &arg.type_name {
+            Some(type_name) => builder.type_strategy(type_name, arg.strategy.clone()),
+            None => builder.strategy(arg.strategy.clone()),
+        };
+    }
+    if let Some(key) = read_key(&opts.key)? {
+        builder = builder.key(key);
+    }
+    Ok(builder.build()?)
+}
+
+/// Compiles the patterns of `types`, leaving out the types of the
+/// `--deny-list` and `--dictionary` files, which need no pattern.
+fn compile_patterns(
+    types: &[String],
+    patterns: &PatternOpts,
+    lists: &ListOpts,
+) -> anyhow::Result<Vec<CompiledPattern>> {
+    let mut listed: Vec<&str> = lists
+        .deny_list
+        .iter()
+        .map(|arg| arg.type_name.as_deref().unwrap_or("denied"))
+        .collect();
+    for arg in &lists.dictionary {
+        listed.push(match arg.kind {
+            WordKind::Organisation => raf::dictionary::ORGANISATION_TYPE,
+            _ => raf::dictionary::NAME_TYPE,
+        });
+    }
+    let types: Vec<String> = types
+        .iter()
+        .filter(|type_name| !listed.contains(&type_name.as_str()))
+        .cloned()
+        .collect();
+    if types.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(PatternSet::load(&patterns.files)?.compile(&types)?)
+}
+
+/// Adds the `--allow-list`, `--deny-list` and `--dictionary` files to
+/// `builder`, one deny-list per type.
+fn add_value_lists(
+    mut builder: RedactorBuilder,
+    opts: &ListOpts,
+) -> anyhow::Result<RedactorBuilder> {
+    for arg in &opts.allow_list {
+        let list = ValueList::read(&arg.path)?;
+        builder = builder.allow_list(AllowList::new(list, arg.type_name.clone()));
+    }
+    let mut deny_lists: BTreeMap<&str, ValueList> = BTreeMap::new();
+    for arg in &opts.deny_list {
+        let type_name = arg.type_name.as_deref().unwrap_or("denied");
+        deny_lists
+            .entry(type_name)
+            .or_default()
+            .extend(ValueList::read(&arg.path)?);
+    }
+    for (type_name, list) in deny_lists {
+        builder = builder.deny_list(type_name, DenyList::new(list));
+    }
+    let mut word_lists = Vec::new();
+    for arg in &opts.dictionary {
+        let list = WordList::read(&arg.path, arg.kind, arg.locale.clone())?;
+        if list.in_locales(&opts.locale) {
+            word_lists.push(list);
+        }
+    }
+    Ok(builder.dictionary(&word_lists))
+}
+
+/// Reads the `hmac` key from `--key-file`, else from the `--key-env` variable.
+fn read_key(opts: &KeyOpts) -> anyhow::Result<Option<Vec<u8>>> {
+    match &opts.key_file {
+        Some(path) => Ok(Some(read_secret_file(path)?)),
+        None => Ok(env::var_os(&opts.key_env)
+            .map(|value| value.to_string_lossy().into_owned().into_bytes())),
+    }
+}
+
+/// Reads a key file, ignoring a trailing newline.
+fn read_secret_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut key = fs::read(path).map_err(|source| raf::RafError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    while matches!(key.last(), Some(b'\n' | b'\r')) {
+        key.pop();
     }
+    Ok(key)
 }
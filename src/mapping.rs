@@ -0,0 +1,212 @@
+//! The unredact mapping: what was redacted, where, and by which pattern.
+//!
+//! Mappings are written as `{"version": 2, "records": [...]}`. Version 1
+//! mappings, a bare array of records with only `unredacted_text` and
+//! `redacted_text`, are still read and restored, without the location checks
+//! of [`Unredactor`](crate::Unredactor).
+
+use crate::error::{RafError, Result};
+use crate::utils;
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Version of the mapping schema written by this version of raf.
+pub const MAPPING_VERSION: u32 = 2;
+
+/// One redacted match, as written to the `-unredact.json` mapping.
+#[derive(Debug, Deserialize, PartialEq, Serialize, Clone, Default)]
+pub struct RedactedData {
+    pub unredacted_text: String,
+    pub redacted_text: String,
+    /// `id` of the pattern that matched, or its index in declaration order
+    /// when it has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_id: Option<String>,
+    /// Main type name of the pattern that matched, e.g. `email`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    /// Text around the match, recorded only when the [`Redactor`] is built
+    /// with [`RedactorBuilder::context`] and never written to mappings.
+    ///
+    /// [`Redactor`]: crate::Redactor
+    /// [`RedactorBuilder::context`]: crate::RedactorBuilder::context
+    #[serde(skip)]
+    pub context: Option<MatchContext>,
+}
+
+/// The text around a match, before and after redaction, for review.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MatchContext {
+    /// Original text just before the match.
+    pub before: String,
+    /// Original text just after the match.
+    pub after: String,
+    /// Redacted text just before the replacement.
+    pub redacted_before: String,
+    /// Redacted text just after the replacement.
+    pub redacted_after: String,
+    /// The replacement as written to the document, which may differ from
+    /// the token recorded in the mapping, e.g. `[REDACTED:<token>]`.
+    pub replacement: String,
+}
+
+/// Where a match was found, within the piece of text handed to the
+/// [`Redactor`](crate::Redactor): the whole file for plain text, one run of
+/// a paragraph for `.docx`, one string operand for PDF.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize, Clone, Default)]
+pub struct Location {
+    /// Byte offset of the start of the match.
+    pub start: usize,
+    /// Byte offset just past the end of the match.
+    pub end: usize,
+    /// Offset of the start of the match, in characters.
+    pub char_start: usize,
+    /// Offset just past the end of the match, in characters.
+    pub char_end: usize,
+    /// Line of the start of the match, from 1.
+    pub line: usize,
+    /// Column of the start of the match, in characters from 1.
+    pub column: usize,
+    /// Which piece of the document the text is, for formats that split it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<DocumentPart>,
+}
+
+impl fmt::Display for Location {
+    /// `line:column`, after the document part if any, e.g.
+    /// `paragraph 3, run 0, 1:12`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.part {
+            Some(DocumentPart::Docx { paragraph, run }) => {
+                write!(f, "paragraph {paragraph}, run {run}, ")?
+            }
+            Some(DocumentPart::Pdf {
+                page,
+                operator,
+                operand,
+            }) => write!(f, "page {page}, operator {operator}, operand {operand}, ")?,
+            None => {}
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A piece of a document redacted separately from the rest.
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Serialize, Clone)]
+#[serde(tag = "format", rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DocumentPart {
+    /// A text run of a `.docx` paragraph, both counted from 0.
+    Docx { paragraph: usize, run: usize },
+    /// A string operand of a `Tj` or `TJ` operator of a PDF page, the page
+    /// counted from 1, the operator and the operand, in the order the strings
+    /// of a `TJ` array are drawn, from 0.
+    Pdf {
+        page: u32,
+        operator: usize,
+        operand: usize,
+    },
+}
+
+#[derive(Serialize)]
+struct MappingOut<'a> {
+    version: u32,
+    records: &'a [RedactedData],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MappingIn {
+    Versioned {
+        version: u32,
+        records: Vec<RedactedData>,
+    },
+    V1(Vec<RedactedData>),
+}
+
+/// Serializes `records` in the current mapping schema.
+pub(crate) fn to_json(records: &[RedactedData]) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec_pretty(&MappingOut {
+        version: MAPPING_VERSION,
+        records,
+    })
+}
+
+/// Deserializes a mapping of any known version of the schema.
+pub(crate) fn from_json(json: &[u8]) -> serde_json::Result<Vec<RedactedData>> {
+    match serde_json::from_slice(json)? {
+        MappingIn::Versioned { version, records } if version <= MAPPING_VERSION => Ok(records),
+        MappingIn::Versioned { version, .. } => Err(serde_json::Error::custom(format!(
+            "mapping version {version} is newer than the supported version {MAPPING_VERSION}"
+        ))),
+        MappingIn::V1(records) => Ok(records),
+    }
+}
+
+/// Writes the unredact mapping to `mapping_path`, readable by its owner only.
+pub fn write_mapping(all_redacted_data: &[RedactedData], mapping_path: &Path) -> Result<()> {
+    let json = to_json(all_redacted_data).map_err(|source| RafError::MappingWrite {
+        path: mapping_path.to_path_buf(),
+        source,
+    })?;
+    utils::write_private(mapping_path, &json)
+}
+
+/// Reads the unredact mapping written by [`write_mapping`].
+pub fn read_mapping(mapping_path: &Path) -> Result<Vec<RedactedData>> {
+    let json = fs::read(mapping_path).map_err(|source| RafError::Io {
+        path: mapping_path.to_path_buf(),
+        source,
+    })?;
+    from_json(&json).map_err(|source| RafError::MappingRead {
+        path: mapping_path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_v1_mapping() {
+        let v1 = br#"[{"unredacted_text": "a@b.com", "redacted_text": "xyz"}]"#;
+        let records = from_json(v1).unwrap();
+        assert_eq!(records[0].redacted_text, "xyz");
+        assert_eq!(records[0].location, None);
+    }
+
+    #[test]
+    fn test_round_trip_v2_mapping() {
+        let records = vec![RedactedData {
+            unredacted_text: "a@b.com".to_owned(),
+            redacted_text: "[EMAIL]".to_owned(),
+            pattern_id: Some("0".to_owned()),
+            type_name: Some("email".to_owned()),
+            location: Some(Location {
+                start: 4,
+                end: 11,
+                char_start: 4,
+                char_end: 11,
+                line: 1,
+                column: 5,
+                part: Some(DocumentPart::Docx {
+                    paragraph: 2,
+                    run: 0,
+                }),
+            }),
+            ..RedactedData::default()
+        }];
+        let json = to_json(&records).unwrap();
+        let text = String::from_utf8(json.clone()).unwrap();
+        assert!(text.contains(r#""version": 2"#));
+        assert!(text.contains(r#""format": "docx""#));
+        assert_eq!(from_json(&json).unwrap(), records);
+        assert!(from_json(br#"{"version": 99, "records": []}"#).is_err());
+    }
+}
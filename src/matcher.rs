@@ -0,0 +1,541 @@
+use crate::lists::AllowList;
+use crate::utils;
+use crate::validate::Validator;
+use crate::Strategy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// How to choose between matches of different patterns that overlap.
+///
+/// Matches are ranked by the policy and accepted best first; a match that
+/// overlaps an already accepted one is dropped. Remaining ties are broken by
+/// the earlier start offset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// The longest match wins, then the first declared pattern.
+    #[default]
+    Longest,
+    /// The match of the pattern declared first wins.
+    FirstDeclared,
+    /// The match of the pattern with the highest priority wins, then the
+    /// longest match, then the first declared pattern.
+    HighestPriority,
+}
+
+impl FromStr for OverlapPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "longest" => Ok(OverlapPolicy::Longest),
+            "first" => Ok(OverlapPolicy::FirstDeclared),
+            "priority" => Ok(OverlapPolicy::HighestPriority),
+            _ => Err(format!(
+                "unknown overlap policy `{s}`, expected `longest`, `first` or `priority`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OverlapPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverlapPolicy::Longest => "longest",
+            OverlapPolicy::FirstDeclared => "first",
+            OverlapPolicy::HighestPriority => "priority",
+        })
+    }
+}
+
+/// Score added to a match by a positive keyword of its pattern nearby.
+pub const KEYWORD_BOOST: f64 = 0.35;
+
+/// Score a match needs to be redacted unless set otherwise, see
+/// [`Matcher::with_min_score`].
+pub const DEFAULT_MIN_SCORE: f64 = 0.5;
+
+/// Finds matches in text some other way than with a regex, e.g. in a list
+/// of words.
+pub trait Detector: fmt::Debug + Send + Sync {
+    /// The byte ranges of the matches in `text`, which may overlap.
+    fn find(&self, text: &str) -> Vec<Range<usize>>;
+
+    /// What the detector finds, shown in reports in place of a regex.
+    fn describe(&self) -> String;
+}
+
+/// How a pattern finds its matches.
+#[derive(Debug, Clone)]
+pub enum Finder {
+    Regex(Regex),
+    Detector(Arc<dyn Detector>),
+}
+
+impl fmt::Display for Finder {
+    /// The regex, or the description of the detector.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finder::Regex(regex) => f.write_str(regex.as_str()),
+            Finder::Detector(detector) => f.write_str(&detector.describe()),
+        }
+    }
+}
+
+/// A compiled pattern along with what raf needs to know about it.
+#[derive(Debug, Clone)]
+pub struct CompiledPattern {
+    pub finder: Finder,
+    /// Identifier of the pattern, recorded in the mapping.
+    pub id: Option<String>,
+    /// Names of the type of PII the pattern finds, e.g. `email` and
+    /// `emails`. The first one is used in labels and reports.
+    pub types: Vec<String>,
+    /// Priority used by [`OverlapPolicy::HighestPriority`].
+    pub priority: i32,
+    /// Replacement strategy of this pattern, overriding the default one.
+    pub strategy: Option<Strategy>,
+    /// What the pattern finds, for reports.
+    pub description: Option<String>,
+    /// Capture group to redact instead of the whole match.
+    pub group: Option<CaptureGroup>,
+    /// Check a match must pass to be redacted.
+    pub validator: Option<Validator>,
+    /// Confidence in a match, from 0 to 1, before its keywords are taken
+    /// into account.
+    pub score: f64,
+    /// Words near a match that raise or suppress its score.
+    pub keywords: Option<ContextKeywords>,
+    /// Type whose [`FakeGenerator`](crate::fake::FakeGenerator) replaces the
+    /// matches under [`Strategy::Fake`], instead of the pattern's own type.
+    pub generator: Option<String>,
+}
+
+/// Words around a match telling whether it is what its pattern looks for,
+/// e.g. `tel` before a phone number and `invoice` before an order number.
+/// Words are matched whole and regardless of case.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ContextKeywords {
+    /// Words adding [`KEYWORD_BOOST`] to the score of a match.
+    #[serde(default)]
+    pub positive: Vec<String>,
+    /// Words suppressing a match whatever its score.
+    #[serde(default)]
+    pub negative: Vec<String>,
+    /// How many characters before and after a match, on the same line, to
+    /// look for the words in.
+    #[serde(default = "default_window")]
+    pub window: usize,
+}
+
+fn default_window() -> usize {
+    40
+}
+
+impl Default for ContextKeywords {
+    fn default() -> Self {
+        ContextKeywords {
+            positive: Vec::new(),
+            negative: Vec::new(),
+            window: default_window(),
+        }
+    }
+}
+
+impl ContextKeywords {
+    /// The score of the match of `text` from byte `start` to `end`, `base`
+    /// boosted by a positive word nearby, or `0` next to a negative one.
+    pub fn score(&self, base: f64, text: &str, start: usize, end: usize) -> f64 {
+        let (before, after) = utils::text_around(text, start, end, self.window);
+        // Words on other lines are about something else.
+        let before = before.rsplit('\n').next().unwrap_or_default();
+        let after = after.split('\n').next().unwrap_or_default();
+        let around = format!("{before} {after}").to_lowercase();
+        if has_word(&around, &self.negative) {
+            0.0
+        } else if has_word(&around, &self.positive) {
+            (base + KEYWORD_BOOST).min(1.0)
+        } else {
+            base
+        }
+    }
+}
+
+/// Returns `true` if one of `words` is in the lowercase `text`, not as part
+/// of a longer word.
+fn has_word(text: &str, words: &[String]) -> bool {
+    words.iter().any(|word| {
+        let word = word.to_lowercase();
+        !word.is_empty()
+            && text.match_indices(&word).any(|(idx, _)| {
+                let before = text[..idx].chars().next_back();
+                let after = text[idx + word.len()..].chars().next();
+                !before.is_some_and(char::is_alphanumeric)
+                    && !after.is_some_and(char::is_alphanumeric)
+            })
+    })
+}
+
+/// A capture group of a pattern, by index or by name.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CaptureGroup {
+    Index(usize),
+    Name(String),
+}
+
+impl CaptureGroup {
+    /// Returns `true` if `regex` has this group.
+    pub fn exists_in(&self, regex: &Regex) -> bool {
+        match self {
+            CaptureGroup::Index(index) => *index < regex.captures_len(),
+            CaptureGroup::Name(name) => regex.capture_names().flatten().any(|n| n == name),
+        }
+    }
+
+    fn get<'t>(&self, captures: &regex::Captures<'t>) -> Option<regex::Match<'t>> {
+        match self {
+            CaptureGroup::Index(index) => captures.get(*index),
+            CaptureGroup::Name(name) => captures.name(name),
+        }
+    }
+}
+
+impl fmt::Display for CaptureGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureGroup::Index(index) => write!(f, "{index}"),
+            CaptureGroup::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl CompiledPattern {
+    /// A pattern without an id or type, with priority `0`, score `1` and the
+    /// default strategy, redacting whole matches.
+    pub fn new(regex: Regex) -> Self {
+        CompiledPattern::with_finder(Finder::Regex(regex))
+    }
+
+    /// A pattern finding its matches with `detector`, otherwise as
+    /// [`new`](Self::new).
+    pub fn detector<D: Detector + 'static>(detector: D) -> Self {
+        CompiledPattern::with_finder(Finder::Detector(Arc::new(detector)))
+    }
+
+    fn with_finder(finder: Finder) -> Self {
+        CompiledPattern {
+            finder,
+            id: None,
+            types: Vec::new(),
+            priority: 0,
+            strategy: None,
+            description: None,
+            group: None,
+            validator: None,
+            score: 1.0,
+            keywords: None,
+            generator: None,
+        }
+    }
+
+    /// The regex of the pattern, unless it has a detector.
+    pub fn regex(&self) -> Option<&Regex> {
+        match &self.finder {
+            Finder::Regex(regex) => Some(regex),
+            Finder::Detector(_) => None,
+        }
+    }
+
+    /// The byte ranges of the matches of the pattern in `text`, only those
+    /// of its capture group if it has one, before they are validated and
+    /// scored.
+    fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        match (&self.finder, &self.group) {
+            (Finder::Regex(regex), None) => regex.find_iter(text).map(|m| m.range()).collect(),
+            (Finder::Regex(regex), Some(group)) => regex
+                .captures_iter(text)
+                .filter_map(|captures| group.get(&captures))
+                .map(|m| m.range())
+                .collect(),
+            (Finder::Detector(detector), _) => detector.find(text),
+        }
+    }
+
+    /// The score of the match of `text` from byte `start` to `end`.
+    pub fn score_match(&self, text: &str, start: usize, end: usize) -> f64 {
+        match &self.keywords {
+            Some(keywords) => keywords.score(self.score, text, start, end),
+            None => self.score,
+        }
+    }
+
+    /// The main type name of the pattern, `redacted` when it has none.
+    pub fn type_name(&self) -> &str {
+        self.types.first().map_or("redacted", String::as_str)
+    }
+}
+
+/// A match of one of the [`Matcher`]'s patterns, as byte offsets into the
+/// searched text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternMatch {
+    pub start: usize,
+    pub end: usize,
+    /// Index of the pattern, in declaration order.
+    pub pattern: usize,
+}
+
+impl PatternMatch {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn overlaps(&self, other: &PatternMatch) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// Finds the matches of several patterns in one text, without overlaps.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    patterns: Vec<CompiledPattern>,
+    policy: OverlapPolicy,
+    min_score: f64,
+    allow_lists: Vec<AllowList>,
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Matcher {
+            patterns: Vec::new(),
+            policy: OverlapPolicy::default(),
+            min_score: DEFAULT_MIN_SCORE,
+            allow_lists: Vec::new(),
+        }
+    }
+}
+
+impl Matcher {
+    pub fn new(policy: OverlapPolicy) -> Self {
+        Matcher {
+            policy,
+            ..Matcher::default()
+        }
+    }
+
+    /// Sets the score a match needs to be found, [`DEFAULT_MIN_SCORE`] by
+    /// default. Matches scored `0`, next to a negative keyword, are never
+    /// found.
+    pub fn with_min_score(mut self, min_score: f64) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    pub fn min_score(&self) -> f64 {
+        self.min_score
+    }
+
+    /// Adds a pattern after the ones already declared.
+    pub fn push(&mut self, pattern: CompiledPattern) {
+        self.patterns.push(pattern);
+    }
+
+    /// Adds a list of values that are never matched.
+    pub fn push_allow_list(&mut self, list: AllowList) {
+        self.allow_lists.push(list);
+    }
+
+    /// The patterns, in declaration order; [`PatternMatch::pattern`] indexes
+    /// into it.
+    pub fn patterns(&self) -> &[CompiledPattern] {
+        &self.patterns
+    }
+
+    /// Identifier of the `index`-th pattern recorded in mappings and reports:
+    /// its `id`, or else its index.
+    pub fn pattern_id(&self, index: usize) -> String {
+        self.patterns[index]
+            .id
+            .clone()
+            .unwrap_or_else(|| index.to_string())
+    }
+
+    pub fn policy(&self) -> OverlapPolicy {
+        self.policy
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns the non-overlapping matches of all patterns in `text`, ordered
+    /// by start offset. Matches failing their pattern's validator, in an
+    /// allow-list or scored below the minimum score are dropped before
+    /// overlaps are resolved, so they hide no other match.
+    pub fn find(&self, text: &str) -> Vec<PatternMatch> {
+        let mut candidates: Vec<PatternMatch> = self
+            .patterns
+            .iter()
+            .enumerate()
+            .flat_map(|(pattern, compiled)| {
+                compiled
+                    .find_spans(text)
+                    .into_iter()
+                    .filter(|span| !span.is_empty())
+                    .filter(|span| {
+                        let value = &text[span.clone()];
+                        compiled
+                            .validator
+                            .is_none_or(|validator| validator.is_valid(value))
+                            && !self
+                                .allow_lists
+                                .iter()
+                                .any(|list| list.allows(&compiled.types, value))
+                    })
+                    .filter(|span| {
+                        let score = compiled.score_match(text, span.start, span.end);
+                        score > 0.0 && score >= self.min_score
+                    })
+                    .map(move |span| PatternMatch {
+                        start: span.start,
+                        end: span.end,
+                        pattern,
+                    })
+            })
+            .collect();
+
+        match self.policy {
+            OverlapPolicy::Longest => {
+                candidates.sort_by_key(|m| (Reverse(m.len()), m.pattern, m.start))
+            }
+            OverlapPolicy::FirstDeclared => candidates.sort_by_key(|m| (m.pattern, m.start)),
+            OverlapPolicy::HighestPriority => candidates.sort_by_key(|m| {
+                (
+                    Reverse(self.patterns[m.pattern].priority),
+                    Reverse(m.len()),
+                    m.pattern,
+                    m.start,
+                )
+            }),
+        }
+
+        let mut accepted: Vec<PatternMatch> = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            // `accepted` is kept sorted by start, so only the neighbours of the
+            // insertion point can overlap the candidate.
+            let idx = accepted.partition_point(|m| m.start < candidate.start);
+            let clashes_before = idx > 0 && accepted[idx - 1].overlaps(&candidate);
+            let clashes_after = idx < accepted.len() && accepted[idx].overlaps(&candidate);
+            if !clashes_before && !clashes_after {
+                accepted.insert(idx, candidate);
+            }
+        }
+        accepted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(policy: OverlapPolicy, patterns: &[(&str, i32)]) -> Matcher {
+        let mut matcher = Matcher::new(policy);
+        for (pattern, priority) in patterns {
+            matcher.push(CompiledPattern {
+                priority: *priority,
+                ..CompiledPattern::new(Regex::new(pattern).unwrap())
+            });
+        }
+        matcher
+    }
+
+    fn spans(matcher: &Matcher, text: &str) -> Vec<(usize, usize, usize)> {
+        matcher
+            .find(text)
+            .iter()
+            .map(|m| (m.start, m.end, m.pattern))
+            .collect()
+    }
+
+    const EMAIL: &str = r"[a-z0-9.]+@[a-z]+\.com";
+    const PHONE: &str = r"\d{8}";
+    const TEXT: &str = "mail 91234567@ex.com or call 87654321";
+
+    #[test]
+    fn test_longest_wins() {
+        let matcher = matcher(OverlapPolicy::Longest, &[(PHONE, 0), (EMAIL, 0)]);
+        assert_eq!(spans(&matcher, TEXT), vec![(5, 20, 1), (29, 37, 0)]);
+    }
+
+    #[test]
+    fn test_first_declared_wins() {
+        let matcher = matcher(OverlapPolicy::FirstDeclared, &[(PHONE, 0), (EMAIL, 0)]);
+        assert_eq!(spans(&matcher, TEXT), vec![(5, 13, 0), (29, 37, 0)]);
+    }
+
+    #[test]
+    fn test_highest_priority_wins() {
+        let matcher = matcher(OverlapPolicy::HighestPriority, &[(EMAIL, 0), (PHONE, 5)]);
+        assert_eq!(spans(&matcher, TEXT), vec![(5, 13, 1), (29, 37, 1)]);
+    }
+
+    #[test]
+    fn test_policy_from_str() {
+        assert_eq!("first".parse(), Ok(OverlapPolicy::FirstDeclared));
+        assert!("shortest".parse::<OverlapPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_capture_group() {
+        let mut matcher = Matcher::new(OverlapPolicy::Longest);
+        matcher.push(CompiledPattern {
+            group: Some(CaptureGroup::Name("value".to_owned())),
+            ..CompiledPattern::new(Regex::new(r"password=(?P<value>\S+)").unwrap())
+        });
+        assert_eq!(spans(&matcher, "password=hunter2 ok"), vec![(9, 16, 0)]);
+        assert!(CaptureGroup::Index(1).exists_in(matcher.patterns()[0].regex().unwrap()));
+        assert!(!CaptureGroup::Index(2).exists_in(matcher.patterns()[0].regex().unwrap()));
+    }
+
+    #[test]
+    fn test_validator_drops_matches() {
+        let mut matcher = Matcher::new(OverlapPolicy::Longest);
+        matcher.push(CompiledPattern {
+            validator: Some(Validator::Nric),
+            ..CompiledPattern::new(Regex::new(r"[STFG]\d{7}[A-Z]").unwrap())
+        });
+        matcher.push(CompiledPattern::new(Regex::new(r"\d{7}").unwrap()));
+        // The invalid NRIC leaves its digits to the other pattern.
+        assert_eq!(
+            spans(&matcher, "S1234567D and S1234567A"),
+            vec![(0, 9, 0), (15, 22, 1)]
+        );
+    }
+
+    #[test]
+    fn test_keywords_score_matches() {
+        let mut matcher = Matcher::new(OverlapPolicy::Longest);
+        matcher.push(CompiledPattern {
+            score: 0.4,
+            keywords: Some(ContextKeywords {
+                positive: vec!["tel".to_owned(), "call".to_owned()],
+                negative: vec!["invoice".to_owned()],
+                window: 10,
+            }),
+            ..CompiledPattern::new(Regex::new(PHONE).unwrap())
+        });
+        let text = "Tel: 91234567, order 87654321, telephone 81234567";
+        assert_eq!(spans(&matcher, text), vec![(5, 13, 0)]);
+
+        let matcher = matcher.with_min_score(0.3);
+        assert_eq!(spans(&matcher, text).len(), 3);
+        // Negative keywords win over positive ones and any threshold.
+        let text = "call about invoice 91234567";
+        assert!(spans(&matcher.with_min_score(0.0), text).is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::error::{RafError, Result};
+use crate::mapping::{self, RedactedData};
+use crate::store::MappingStore;
+use crate::utils;
+use crate::vault::MappingVault;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where redacted files are written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OutputLocation {
+    /// Into a `redacted` folder next to each input file.
+    #[default]
+    Sibling,
+    /// Under `root`, mirroring the directory tree of the input.
+    Mirror(PathBuf),
+    /// Over the input file itself, first copying it to `<file><suffix>` when
+    /// a backup suffix is given.
+    InPlace { backup_suffix: Option<String> },
+}
+
+/// Decides where the redacted file and the mapping of each input are written.
+#[derive(Debug, Clone, Default)]
+pub struct OutputPlan {
+    /// Root of the input tree; paths below it are mirrored into the output
+    /// and mapping directories.
+    pub input_root: PathBuf,
+    pub location: OutputLocation,
+    /// Directory for the `-unredact.json` mappings, mirrored like the output.
+    /// Defaults to the directory of the redacted file.
+    pub mapping_dir: Option<PathBuf>,
+    /// Overwrite existing outputs, mappings and backups.
+    pub force: bool,
+    /// Encrypt the mappings into `-unredact.vault` files instead of writing
+    /// them as plain `-unredact.json`.
+    pub vault: Option<MappingVault>,
+    /// Append all the mappings to one store instead of writing a mapping per
+    /// file. The store is written in clear, so it cannot be combined with a
+    /// [`vault`](Self::vault).
+    pub store: Option<Arc<MappingStore>>,
+}
+
+/// Paths written for one input file, see [`OutputPlan::paths_for`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputPaths {
+    pub output: PathBuf,
+    pub mapping: PathBuf,
+    pub backup: Option<PathBuf>,
+}
+
+impl OutputPlan {
+    /// Redacted files go into a `redacted` folder next to each input.
+    pub fn sibling(input_root: impl Into<PathBuf>) -> Self {
+        OutputPlan {
+            input_root: input_root.into(),
+            ..OutputPlan::default()
+        }
+    }
+
+    /// Computes where the redacted counterpart of `path` and its mapping go.
+    pub fn paths_for(&self, path: &Path) -> Result<OutputPaths> {
+        let relative_dir = path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(&self.input_root).ok())
+            .unwrap_or_else(|| Path::new(""));
+        let parent = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let (output_dir, backup) = match &self.location {
+            OutputLocation::Sibling => (parent.join("redacted"), None),
+            OutputLocation::Mirror(root) => (root.join(relative_dir), None),
+            OutputLocation::InPlace { backup_suffix } => {
+                let backup = backup_suffix.as_ref().map(|suffix| {
+                    let mut backup = path.as_os_str().to_os_string();
+                    backup.push(suffix);
+                    PathBuf::from(backup)
+                });
+                (parent.to_path_buf(), backup)
+            }
+        };
+        let mapping_dir = match &self.mapping_dir {
+            Some(mapping_dir) => mapping_dir.join(relative_dir),
+            None => output_dir.clone(),
+        };
+
+        let mut mapping = utils::get_mapping_file_path(path, &mapping_dir)?;
+        if self.vault.is_some() {
+            mapping.set_extension("vault");
+        }
+        Ok(OutputPaths {
+            output: utils::get_output_file_path(path, &output_dir)?,
+            mapping,
+            backup,
+        })
+    }
+
+    /// Writes the mapping of `input` to `path`, encrypted if the plan has a
+    /// vault, or appends it to the plan's store.
+    pub fn write_mapping(
+        &self,
+        input: &Path,
+        all_redacted_data: &[RedactedData],
+        path: &Path,
+    ) -> Result<()> {
+        match (&self.store, &self.vault) {
+            (Some(_), Some(_)) => Err(RafError::StoreEncrypted),
+            (Some(store), None) => store.append(input, all_redacted_data),
+            (None, Some(vault)) => vault.write(all_redacted_data, path),
+            (None, None) => mapping::write_mapping(all_redacted_data, path),
+        }
+    }
+
+    /// Readies `path` for the mapping of a document read from stdin, which has
+    /// no paths of its own: fails if the mapping exists without
+    /// [`force`](Self::force) and creates its directory. The plan's store
+    /// needs neither.
+    pub fn prepare_mapping(&self, path: &Path) -> Result<()> {
+        if self.store.is_some() {
+            return Ok(());
+        }
+        if !self.force && path.exists() {
+            return Err(RafError::OutputExists(path.to_path_buf()));
+        }
+        create_parent_dir(path)
+    }
+
+    /// Returns `true` if `dir` holds outputs of this plan and must not be
+    /// traversed as input.
+    pub fn is_output_dir(&self, dir: &Path) -> bool {
+        let is_sibling_output = self.location == OutputLocation::Sibling
+            && dir.file_name().and_then(|name| name.to_str()) == Some("redacted");
+        let is_mirror_root = matches!(&self.location, OutputLocation::Mirror(root) if root == dir);
+        is_sibling_output || is_mirror_root || self.mapping_dir.as_deref() == Some(dir)
+    }
+
+    /// Fails if any of `paths` would overwrite an existing file without
+    /// [`force`](Self::force), or if the plan has both a store and a vault.
+    /// Redacting in place always replaces the input.
+    pub(crate) fn check_overwrite(&self, input: &Path, paths: &OutputPaths) -> Result<()> {
+        if self.store.is_some() && self.vault.is_some() {
+            return Err(RafError::StoreEncrypted);
+        }
+        if self.force {
+            return Ok(());
+        }
+        let mapping = match self.store {
+            Some(_) => None,
+            None => Some(&paths.mapping),
+        };
+        let outputs = [Some(&paths.output), mapping, paths.backup.as_ref()];
+        match outputs
+            .into_iter()
+            .flatten()
+            .find(|p| p.as_path() != input && p.exists())
+        {
+            Some(existing) => Err(RafError::OutputExists(existing.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Creates the parent directory of `path` if it does not exist yet.
+pub(crate) fn create_parent_dir(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            fs::create_dir_all(parent).map_err(|source| RafError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::VaultSecret;
+
+    #[test]
+    fn test_sibling_paths() -> Result<()> {
+        let plan = OutputPlan::sibling("in");
+        let paths = plan.paths_for(Path::new("in/a/file1.txt"))?;
+        assert_eq!(paths.output, PathBuf::from("in/a/redacted/file1.txt"));
+        assert_eq!(
+            paths.mapping,
+            PathBuf::from("in/a/redacted/file1-unredact.json")
+        );
+        assert_eq!(paths.backup, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror_paths_with_mapping_dir() -> Result<()> {
+        let plan = OutputPlan {
+            input_root: PathBuf::from("in"),
+            location: OutputLocation::Mirror(PathBuf::from("out")),
+            mapping_dir: Some(PathBuf::from("vault")),
+            ..OutputPlan::default()
+        };
+        let paths = plan.paths_for(Path::new("in/a/b/doc.docx"))?;
+        assert_eq!(paths.output, PathBuf::from("out/a/b/doc.docx"));
+        assert_eq!(paths.mapping, PathBuf::from("vault/a/b/doc-unredact.json"));
+        assert!(plan.is_output_dir(Path::new("out")));
+        assert!(plan.is_output_dir(Path::new("vault")));
+        assert!(!plan.is_output_dir(Path::new("in/redacted")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_place_paths() -> Result<()> {
+        let plan = OutputPlan {
+            input_root: PathBuf::from("in"),
+            location: OutputLocation::InPlace {
+                backup_suffix: Some(".bak".to_owned()),
+            },
+            ..OutputPlan::default()
+        };
+        let paths = plan.paths_for(Path::new("in/file1.txt"))?;
+        assert_eq!(paths.output, PathBuf::from("in/file1.txt"));
+        assert_eq!(paths.backup, Some(PathBuf::from("in/file1.txt.bak")));
+        plan.check_overwrite(Path::new("in/file1.txt"), &paths)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_cannot_be_encrypted() -> Result<()> {
+        let store = std::env::temp_dir().join(format!("raf-plan-{}.jsonl", std::process::id()));
+        let plan = OutputPlan {
+            vault: Some(MappingVault::new(&VaultSecret::KeyFile(vec![7; 32]))),
+            store: Some(Arc::new(MappingStore::open(&store)?)),
+            ..OutputPlan::sibling("in")
+        };
+        let paths = plan.paths_for(Path::new("in/file1.txt"))?;
+        assert!(matches!(
+            plan.check_overwrite(Path::new("in/file1.txt"), &paths),
+            Err(RafError::StoreEncrypted)
+        ));
+        assert!(matches!(
+            plan.write_mapping(
+                Path::new("in/file1.txt"),
+                &[RedactedData::default()],
+                &paths.mapping
+            ),
+            Err(RafError::StoreEncrypted)
+        ));
+        assert_eq!(fs::metadata(&store).unwrap().len(), 0);
+        fs::remove_file(&store).unwrap();
+        Ok(())
+    }
+}
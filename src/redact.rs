@@ -1,138 +1,82 @@
-use crate::{utils::{self, RedactedData}, RED_ERROR_STRING, pdf};
-use anyhow::anyhow;
+use crate::error::{RafError, Result};
+use crate::format::{FormatRedactor, FormatRegistry};
+use crate::mapping::{DocumentPart, RedactedData};
+use crate::output::{self, OutputPlan};
+use crate::Redactor;
 use docx_rs::*;
-use lopdf::Document;
-use regex::Regex;
 use std::fs;
-use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
-pub(crate) fn redact_txt_and_write_json(
-    path: &Path,
-    regex_vec: &[Regex],
-    output_folder: &Path,
-) -> anyhow::Result<()> {
-    let mut all_redacted_data: Vec<RedactedData> = Vec::new();
-    let text = fs::read_to_string(path).expect("Failed to read file.");
-    let (redacted_text, redacted_data) = utils::redact_text_get_data(&text, regex_vec)
-        .map_err(|err| anyhow!("Unable to get redacted text and the unredacted data, {err}"))?;
-    all_redacted_data.extend(redacted_data);
-
-    utils::write_redacted_text(redacted_text, path, output_folder)?;
-    utils::write_redacted_data_json(all_redacted_data, path, output_folder)?;
-    anyhow::Ok(())
-}
-
-pub(crate) fn redact_pdf_and_write_json(
+/// Redacts the file at `path` with the format detected by the `redactor`'s
+/// registry, writing the redacted file and its `-unredact.json` mapping where
+/// `plan` says.
+pub(crate) fn redact_one_file(
     path: &Path,
-    regex_vec: &[Regex],
-    output_folder: &Path,
-) -> anyhow::Result<()> {
-    let mut pdf = Document::load(path)
-        .map_err(|err| anyhow!("{}Unable to load the pdf, {err}", *RED_ERROR_STRING))?;
-    
-    let all_redacted_data = pdf::replace_text(&mut pdf, regex_vec)?;
+    redactor: &Redactor,
+    plan: &OutputPlan,
+) -> Result<Vec<RedactedData>> {
+    let (input, format) = read_input(path, redactor.formats())?;
+    let paths = plan.paths_for(path)?;
+    plan.check_overwrite(path, &paths)?;
 
-    let output_path = output_folder.join(path.file_name().ok_or_else(|| anyhow!(
-        "{} Unable to join {} with the `file_name` of {}",
-        *RED_ERROR_STRING,
-        output_folder.display(),
-        path.display()
-    ))?);
+    // Redact into memory first, so a failure never leaves a truncated output
+    // behind, which matters most when redacting in place.
+    let mut output = Vec::new();
+    let all_redacted_data = format.redact(&input, redactor, &mut output)?;
 
-    pdf.save(&output_path).map_err(|err| {
-        anyhow!(
-            "{}Unable to save pdf to the redacted file path `{}`, {err}",
-            *RED_ERROR_STRING,
-            output_path.display()
-        )
+    if let Some(backup) = &paths.backup {
+        fs::copy(path, backup).map_err(|source| RafError::Io {
+            path: backup.clone(),
+            source,
+        })?;
+    }
+    output::create_parent_dir(&paths.output)?;
+    fs::write(&paths.output, output).map_err(|source| RafError::Io {
+        path: paths.output.clone(),
+        source,
     })?;
-    utils::write_redacted_data_json(all_redacted_data, path, output_folder)?;
-    anyhow::Ok(())
-}
-
-pub(crate) fn redact_one_file(
-    path: &Path,
-    regex_vec: &[Regex],
-    output_folder: &Path,
-) -> anyhow::Result<()> {
-    if let Some(extension) = path.extension() {
-        match extension.to_str() {
-            Some("txt") => redact_txt_and_write_json(path, regex_vec, output_folder),
-            // Some("pdf") => redact_pdf_and_write_json(path, &regex_vec, &output_folder),
-            Some("docx") => redact_docx_and_write_json(path, regex_vec, output_folder),
-            Some(_) => Err(anyhow!(
-                "{}Extension: {:?} not implemented",
-                *RED_ERROR_STRING,
-                extension
-            )),
-            None => Err(anyhow!(
-                "{}Unable to convert `OsStr` to `str`",
-                *RED_ERROR_STRING
-            )),
-        }
-    } else {
-        Err(anyhow!(
-            "{}Extension of path=`{}` not found",
-            *RED_ERROR_STRING,
-            path.display()
-        ))
+    if plan.store.is_none() {
+        output::create_parent_dir(&paths.mapping)?;
     }
+    plan.write_mapping(path, &all_redacted_data, &paths.mapping)?;
+    Ok(all_redacted_data)
 }
 
-pub(crate) fn redact_docx_and_write_json(
+/// Reads the file at `path` and detects its format in `registry`.
+pub(crate) fn read_input<'a>(
     path: &Path,
-    regex_vec: &[Regex],
-    output_folder: &Path,
-) -> anyhow::Result<()> {
-    let mut all_redacted_data: Vec<RedactedData> = Vec::new();
-
-    let mut original_docx = docx_rs::read_docx(&read_to_vec(&path.to_path_buf())?)?;
-    let mut original_docu = original_docx.document; // pluck `document` out
-    for child in original_docu.children.iter_mut() {
-        match child {
-            DocumentChild::Paragraph(para) => replace_matches_in_paragraph(para, regex_vec, &mut all_redacted_data),
-            _ => {},
-        }
-    }
-    original_docx.document = original_docu; // insert `document` back
-    
-    let output_path = output_folder.join(path.file_name().ok_or_else(|| anyhow!(
-        "{} Unable to join {} with the `file_name` of {}",
-        *RED_ERROR_STRING,
-        output_folder.display(),
-        path.display()
-    ))?);
-
-    let file = fs::File::create(output_path).map_err(|err| {
-        anyhow!(
-            "{}Unable to create the redacted text file, {err}",
-            *RED_ERROR_STRING
-        )
+    registry: &'a FormatRegistry,
+) -> Result<(Vec<u8>, &'a dyn FormatRedactor)> {
+    let input = fs::read(path).map_err(|source| RafError::Io {
+        path: path.to_path_buf(),
+        source,
     })?;
-
-    original_docx.build().pack(file).map_err(|err| {
-        anyhow!(
-            "{}Unable to pack the output_docx into a `zip` file, {err}",
-            *RED_ERROR_STRING,
-        )
-    })?;
-    utils::write_redacted_data_json(all_redacted_data, path, output_folder)?;
-    anyhow::Ok(())
+    let format = registry
+        .detect(path, &input)
+        .ok_or_else(|| RafError::UnsupportedFile {
+            path: path.to_path_buf(),
+            supported: registry.names(),
+        })?;
+    Ok((input, format))
 }
 
-fn read_to_vec(file_name: &PathBuf) -> anyhow::Result<Vec<u8>> {
-    let mut buf = Vec::new();
-    std::fs::File::open(file_name)?.read_to_end(&mut buf)?;
-    Ok(buf)
-}
-
-/// Use in `.docx` files. 
-pub(crate) fn replace_matches_in_paragraph(
+/// Replaces the text of every run of `para`, the `paragraph`-th of its
+/// document, with the result of `f`.
+///
+/// Use in `.docx` files.
+pub(crate) fn map_paragraph_text(
     para: &mut docx_rs::Paragraph,
-    regex_vec: &[Regex],
-    all_redacted_data: &mut Vec<RedactedData>,
-) {
+    paragraph: usize,
+    f: &mut dyn FnMut(&str, DocumentPart) -> Result<String>,
+) -> Result<()> {
+    let mut run = 0;
+    let mut part = || {
+        run += 1;
+        DocumentPart::Docx {
+            paragraph,
+            run: run - 1,
+        }
+    };
     // For now support only run and ins.
     for c in para.children.iter_mut() {
         match c {
@@ -141,11 +85,7 @@ pub(crate) fn replace_matches_in_paragraph(
                     if let InsertChild::Run(r) = c {
                         for c in r.children.iter_mut() {
                             if let RunChild::Text(t) = c {
-                                let (redacted_text, redacted_data) =
-                                    utils::redact_text_get_data(&t.text, regex_vec)
-                                        .unwrap_or_default();
-                                t.text = redacted_text;
-                                all_redacted_data.extend(redacted_data);
+                                t.text = f(&t.text, part())?;
                             }
                         }
                     }
@@ -154,14 +94,12 @@ pub(crate) fn replace_matches_in_paragraph(
             ParagraphChild::Run(run) => {
                 for c in run.children.iter_mut() {
                     if let RunChild::Text(t) = c {
-                        let (redacted_text, redacted_data) =
-                            utils::redact_text_get_data(&t.text, regex_vec).unwrap_or_default();
-                        t.text = redacted_text;
-                        all_redacted_data.extend(redacted_data);
+                        t.text = f(&t.text, part())?;
                     }
                 }
             }
             _ => {}
         }
     }
+    Ok(())
 }
@@ -0,0 +1,550 @@
+use crate::dictionary::{Dictionary, WordList, NAME_TYPE, ORGANISATION_TYPE};
+use crate::error::{RafError, Result};
+use crate::fake::{FakeGenerator, GeneratorRegistry};
+use crate::format::{FormatRedactor, FormatRegistry};
+use crate::lists::{AllowList, DenyList};
+use crate::mapping::{DocumentPart, RedactedData};
+use crate::matcher::{CompiledPattern, Matcher, OverlapPolicy};
+use crate::output::OutputPlan;
+use crate::patterns;
+use crate::redact;
+use crate::strategy::{Strategy, StrategyState};
+use crate::utils;
+use regex::Regex;
+use regex_syntax::ParserBuilder;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Redacts text and documents with a set of patterns.
+///
+/// ```no_run
+/// let redactor = raf::Redactor::builder()
+///     .patterns([r"[STFG]\d{7}[A-Z]"])
+///     .strategy(raf::Strategy::Random)
+///     .build()?;
+/// let (redacted, mapping) = redactor.redact_bytes(b"NRIC: S1234567D", "txt")?;
+/// # Ok::<(), raf::RafError>(())
+/// ```
+pub struct Redactor {
+    matcher: Matcher,
+    strategy: Strategy,
+    type_strategies: HashMap<String, Strategy>,
+    state: StrategyState,
+    registry: FormatRegistry,
+    context: usize,
+}
+
+impl Redactor {
+    /// Starts building a [`Redactor`].
+    pub fn builder() -> RedactorBuilder {
+        RedactorBuilder::default()
+    }
+
+    /// The default strategy used to replace matches.
+    pub fn strategy(&self) -> &Strategy {
+        &self.strategy
+    }
+
+    /// The strategy used for matches of `pattern`: the one set for any of its
+    /// types with [`RedactorBuilder::type_strategy`], else the pattern's own,
+    /// else the default one.
+    pub fn strategy_for<'a>(&'a self, pattern: &'a CompiledPattern) -> &'a Strategy {
+        pattern
+            .types
+            .iter()
+            .find_map(|t| self.type_strategies.get(t))
+            .or(pattern.strategy.as_ref())
+            .unwrap_or(&self.strategy)
+    }
+
+    /// The formats this redactor can handle.
+    pub fn formats(&self) -> &FormatRegistry {
+        &self.registry
+    }
+
+    /// The patterns' matcher.
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    /// Decrypts a token of [`Strategy::Fpe`] back into the original value.
+    ///
+    /// Fails without a key, or if the token is too short to have been
+    /// encrypted.
+    pub fn decrypt(&self, token: &str) -> Result<String> {
+        if self.state.is_keyless() {
+            return Err(RafError::KeyMissing);
+        }
+        self.state.decrypt(token).ok_or(RafError::FpeTokenInvalid)
+    }
+
+    /// Redacts every match of the patterns in `text`.
+    pub fn redact_text(&self, text: &str) -> Result<(String, Vec<RedactedData>)> {
+        self.redact_text_in(text, None)
+    }
+
+    /// Redacts every match of the patterns in `text`, the `part` of a larger
+    /// document, recording `part` in the location of each match.
+    pub fn redact_part(
+        &self,
+        text: &str,
+        part: DocumentPart,
+    ) -> Result<(String, Vec<RedactedData>)> {
+        self.redact_text_in(text, Some(&part))
+    }
+
+    fn redact_text_in(
+        &self,
+        text: &str,
+        part: Option<&DocumentPart>,
+    ) -> Result<(String, Vec<RedactedData>)> {
+        utils::redact_text_get_data(
+            text,
+            part,
+            &self.matcher,
+            self.context,
+            |pattern, matched| {
+                let strategy = self.strategy_for(pattern);
+                let type_name = match (strategy, &pattern.generator) {
+                    (Strategy::Fake, Some(generator)) => generator,
+                    _ => pattern.type_name(),
+                };
+                strategy.replace(matched, type_name, &self.state)
+            },
+        )
+    }
+
+    /// Redacts an in-memory document of the format named `format`, returning
+    /// the redacted document and the unredact mapping.
+    pub fn redact_bytes(&self, input: &[u8], format: &str) -> Result<(Vec<u8>, Vec<RedactedData>)> {
+        let mut output = Vec::new();
+        let redacted_data = self.format(format)?.redact(input, self, &mut output)?;
+        Ok((output, redacted_data))
+    }
+
+    /// Reads a whole document of the format named `format` from `reader` and
+    /// writes its redacted counterpart to `writer`, returning the unredact
+    /// mapping.
+    pub fn redact_reader<R: Read, W: Write>(
+        &self,
+        mut reader: R,
+        format: &str,
+        mut writer: W,
+    ) -> Result<Vec<RedactedData>> {
+        let format = self.format(format)?;
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input).map_err(RafError::Stream)?;
+        format.redact(&input, self, &mut writer)
+    }
+
+    /// Redacts the file at `path`, writing the redacted file and its
+    /// `-unredact.json` mapping where `plan` says, and returns the mapping.
+    pub fn redact_file(&self, path: &Path, plan: &OutputPlan) -> Result<Vec<RedactedData>> {
+        redact::redact_one_file(path, self, plan)
+    }
+
+    /// Finds the matches in the file at `path` without writing anything,
+    /// returning the mapping redacting it would record.
+    pub fn scan_file(&self, path: &Path) -> Result<Vec<RedactedData>> {
+        let (input, format) = redact::read_input(path, &self.registry)?;
+        format.redact(&input, self, &mut io::sink())
+    }
+
+    fn format(&self, name: &str) -> Result<&dyn FormatRedactor> {
+        self.registry
+            .get(name)
+            .ok_or_else(|| RafError::UnsupportedFormat(name.to_owned()))
+    }
+}
+
+/// Builder for [`Redactor`], see [`Redactor::builder`].
+#[derive(Default)]
+pub struct RedactorBuilder {
+    patterns: Vec<String>,
+    compiled: Vec<CompiledPattern>,
+    overlap_policy: OverlapPolicy,
+    strategy: Strategy,
+    type_strategies: HashMap<String, Strategy>,
+    key: Option<Vec<u8>>,
+    generators: GeneratorRegistry,
+    registry: Option<FormatRegistry>,
+    formats: Vec<Box<dyn FormatRedactor>>,
+    context: usize,
+    min_score: Option<f64>,
+    allow_lists: Vec<AllowList>,
+}
+
+impl RedactorBuilder {
+    /// Adds regex patterns, compiled when [`build`](Self::build) is called.
+    pub fn patterns<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.patterns.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds already compiled regexes.
+    pub fn regexes<I: IntoIterator<Item = Regex>>(mut self, regexes: I) -> Self {
+        self.compiled
+            .extend(regexes.into_iter().map(CompiledPattern::new));
+        self
+    }
+
+    /// Adds compiled patterns along with their types, priorities and
+    /// strategies, e.g. those returned by [`get_pattern_vec`](crate::get_pattern_vec).
+    pub fn compiled_patterns<I: IntoIterator<Item = CompiledPattern>>(
+        mut self,
+        patterns: I,
+    ) -> Self {
+        self.compiled.extend(patterns);
+        self
+    }
+
+    /// Never redacts the values of `list`.
+    pub fn allow_list(mut self, list: AllowList) -> Self {
+        self.allow_lists.push(list);
+        self
+    }
+
+    /// Always redacts the values of `list`, as type `type_name`, whatever
+    /// the patterns.
+    pub fn deny_list(mut self, type_name: impl Into<String>, list: DenyList) -> Self {
+        let type_name = type_name.into();
+        self.compiled.push(CompiledPattern {
+            id: Some(format!("deny-list:{type_name}")),
+            types: vec![type_name],
+            ..CompiledPattern::detector(list)
+        });
+        self
+    }
+
+    /// Redacts the names, as type [`NAME_TYPE`], and organisations, as type
+    /// [`ORGANISATION_TYPE`], of the word lists, whatever the patterns.
+    pub fn dictionary<'a>(mut self, lists: impl IntoIterator<Item = &'a WordList>) -> Self {
+        let lists: Vec<&WordList> = lists.into_iter().collect();
+        let dictionaries = [
+            (NAME_TYPE, Dictionary::names(lists.iter().copied())),
+            (ORGANISATION_TYPE, Dictionary::organisations(lists)),
+        ];
+        for (type_name, dictionary) in dictionaries {
+            if let Some(dictionary) = dictionary {
+                self.compiled.push(CompiledPattern {
+                    id: Some(format!("dictionary:{type_name}")),
+                    types: vec![type_name.to_owned()],
+                    ..CompiledPattern::detector(dictionary)
+                });
+            }
+        }
+        self
+    }
+
+    /// Sets how overlapping matches of different patterns are resolved,
+    /// [`OverlapPolicy::Longest`] by default.
+    pub fn overlap_policy(mut self, policy: OverlapPolicy) -> Self {
+        self.overlap_policy = policy;
+        self
+    }
+
+    /// Sets the score a match needs to be redacted,
+    /// [`DEFAULT_MIN_SCORE`](crate::matcher::DEFAULT_MIN_SCORE) by default.
+    /// Patterns score their matches with [`CompiledPattern::score`] and
+    /// [`CompiledPattern::keywords`].
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Sets the default replacement strategy, [`Strategy::Random`] by default.
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets the replacement strategy of the patterns of type `type_name`,
+    /// overriding the strategy of the patterns themselves.
+    pub fn type_strategy(mut self, type_name: impl Into<String>, strategy: Strategy) -> Self {
+        self.type_strategies.insert(type_name.into(), strategy);
+        self
+    }
+
+    /// Sets the secret key of [`Strategy::Hmac`] and [`Strategy::Fpe`]. With a key,
+    /// [`Strategy::Fake`] also gives the same fake values in every run.
+    pub fn key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Replaces the default set of fake value generators.
+    pub fn generators(mut self, generators: GeneratorRegistry) -> Self {
+        self.generators = generators;
+        self
+    }
+
+    /// Sets the fake value generator of the patterns of type `type_name`.
+    pub fn generator<G: FakeGenerator + 'static>(
+        mut self,
+        type_name: impl Into<String>,
+        generator: G,
+    ) -> Self {
+        self.generators.register(type_name, generator);
+        self
+    }
+
+    /// Replaces the default set of formats.
+    pub fn registry(mut self, registry: FormatRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Records up to `chars` characters of text around each match in
+    /// [`RedactedData::context`], for review. Defaults to `0`, recording
+    /// none.
+    pub fn context(mut self, chars: usize) -> Self {
+        self.context = chars;
+        self
+    }
+
+    /// Registers an additional format on top of the registry.
+    pub fn format<F: FormatRedactor + 'static>(mut self, format: F) -> Self {
+        self.formats.push(Box::new(format));
+        self
+    }
+
+    /// Compiles the patterns and builds the [`Redactor`].
+    pub fn build(self) -> Result<Redactor> {
+        // Compiled patterns are declared before the pattern strings.
+        let mut matcher = Matcher::new(self.overlap_policy);
+        if let Some(min_score) = self.min_score {
+            matcher = matcher.with_min_score(min_score);
+        }
+        for list in self.allow_lists {
+            matcher.push_allow_list(list);
+        }
+        for pattern in self.compiled {
+            matcher.push(pattern);
+        }
+        for pattern in self.patterns {
+            let regex = Regex::new(&pattern).map_err(|error| {
+                patterns::invalid_pattern(
+                    matcher.patterns().len(),
+                    &pattern,
+                    ParserBuilder::new(),
+                    error,
+                )
+            })?;
+            matcher.push(CompiledPattern::new(regex));
+        }
+
+        for (idx, pattern) in matcher.patterns().iter().enumerate() {
+            if let Some(generator) = &pattern.generator {
+                if !self.generators.contains(generator) {
+                    return Err(RafError::UnknownGenerator {
+                        pattern: matcher.pattern_id(idx),
+                        generator: generator.clone(),
+                    });
+                }
+            }
+        }
+
+        let needs_key = std::iter::once(&self.strategy)
+            .chain(self.type_strategies.values())
+            .chain(matcher.patterns().iter().flat_map(|p| p.strategy.as_ref()))
+            .any(|strategy| matches!(strategy, Strategy::Hmac(_) | Strategy::Fpe));
+        if needs_key && self.key.as_ref().is_none_or(|key| key.is_empty()) {
+            return Err(RafError::KeyMissing);
+        }
+
+        let mut registry = self.registry.unwrap_or_default();
+        for format in self.formats {
+            registry.register_boxed(format)?;
+        }
+
+        Ok(Redactor {
+            matcher,
+            strategy: self.strategy,
+            type_strategies: self.type_strategies,
+            state: StrategyState::new(self.key, self.generators),
+            registry,
+            context: self.context,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Validator;
+
+    #[test]
+    fn test_redact_bytes_txt() -> Result<()> {
+        let redactor = Redactor::builder().patterns([r"\d{8}"]).build()?;
+        let (output, redacted_data) = redactor.redact_bytes(b"call 91234567 now", "txt")?;
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("call [REDACTED:"));
+        assert!(!output.contains("91234567"));
+        assert_eq!(redacted_data.len(), 1);
+        assert_eq!(redacted_data[0].unredacted_text, "91234567");
+        Ok(())
+    }
+
+    #[test]
+    fn test_redact_text_overlapping_patterns() -> Result<()> {
+        // The phone pattern used to splice into the text already modified by
+        // the email pattern, at offsets of the original text.
+        let redactor = Redactor::builder()
+            .patterns([r"[a-z0-9.]+@[a-z]+\.com", r"\d{8}"])
+            .build()?;
+        let (output, redacted_data) =
+            redactor.redact_text("a 91234567@ex.com b x@ex.com c 87654321")?;
+        let unredacted: Vec<_> = redacted_data
+            .iter()
+            .map(|data| data.unredacted_text.as_str())
+            .collect();
+        assert_eq!(unredacted, ["91234567@ex.com", "x@ex.com", "87654321"]);
+        let tokens: Vec<_> = redacted_data
+            .iter()
+            .map(|data| format!("[REDACTED:{}]", data.redacted_text))
+            .collect();
+        assert_eq!(
+            output,
+            format!("a {} b {} c {}", tokens[0], tokens[1], tokens[2])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_records_locate_matches() -> Result<()> {
+        let redactor = Redactor::builder()
+            .compiled_patterns([CompiledPattern {
+                id: Some("sg-phone".to_owned()),
+                types: vec!["phone".to_owned()],
+                ..CompiledPattern::new(Regex::new(r"\d{8}").unwrap())
+            }])
+            .build()?;
+        let (_, redacted_data) = redactor.redact_text("née\ncall 91234567")?;
+        let record = &redacted_data[0];
+        assert_eq!(record.pattern_id.as_deref(), Some("sg-phone"));
+        assert_eq!(record.type_name.as_deref(), Some("phone"));
+        let location = record.location.as_ref().unwrap();
+        assert_eq!((location.start, location.end), (10, 18));
+        assert_eq!((location.char_start, location.char_end), (9, 17));
+        assert_eq!((location.line, location.column), (2, 6));
+
+        let part = DocumentPart::Docx {
+            paragraph: 3,
+            run: 1,
+        };
+        let (_, redacted_data) = redactor.redact_part("91234567", part.clone())?;
+        assert_eq!(redacted_data[0].location.as_ref().unwrap().part, Some(part));
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_strategy_overrides_pattern_strategy() -> Result<()> {
+        let pattern = |regex: &str, type_name: &str, strategy| CompiledPattern {
+            types: vec![type_name.to_owned()],
+            strategy,
+            ..CompiledPattern::new(Regex::new(regex).unwrap())
+        };
+        let redactor = Redactor::builder()
+            .compiled_patterns([
+                pattern(r"\w+@\w+\.com", "email", Some(Strategy::Mask)),
+                pattern(r"\d{8}", "phone", Some(Strategy::Mask)),
+            ])
+            .strategy(Strategy::Remove)
+            .type_strategy("phone", Strategy::KeepLast(4))
+            .build()?;
+        let (output, _) = redactor.redact_text("jo@ex.com 91234567 S1234567D")?;
+        assert_eq!(output, "j*@**.com ****4567 S1234567D");
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_pattern() {
+        let err = Redactor::builder().patterns(["(unclosed"]).build();
+        assert!(matches!(
+            err,
+            Err(RafError::PatternInvalid {
+                index: 0,
+                column: Some(1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_hmac_needs_key() -> Result<()> {
+        let builder = || {
+            Redactor::builder()
+                .patterns([r"\d{8}"])
+                .strategy(Strategy::Hmac(16))
+        };
+        assert!(matches!(builder().build(), Err(RafError::KeyMissing)));
+
+        // Separate redactors with the same key agree on the tokens.
+        let first = builder().key("secret").build()?.redact_text("91234567")?;
+        let second = builder().key("secret").build()?.redact_text("91234567")?;
+        assert_eq!(first.0, second.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fpe_decrypts_without_mapping() -> Result<()> {
+        let redactor = Redactor::builder()
+            .patterns([r"\d{4} \d{4}", r"#\d{3}"])
+            .strategy(Strategy::Fpe)
+            .key("secret")
+            .build()?;
+        let (output, _) = redactor.redact_text("call 9123 4567 now")?;
+        let token = &output["call ".len().."call 9123 4567".len()];
+        assert_ne!(token, "9123 4567");
+        assert_eq!(redactor.decrypt(token)?, "9123 4567");
+        // Too short to encrypt, or to be a token.
+        assert!(matches!(
+            redactor.redact_text("ext #123"),
+            Err(RafError::FpeTooShort { .. })
+        ));
+        assert!(matches!(
+            redactor.decrypt("1234"),
+            Err(RafError::FpeTokenInvalid)
+        ));
+
+        let keyless = Redactor::builder().build()?;
+        assert!(matches!(keyless.decrypt(token), Err(RafError::KeyMissing)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_format() -> Result<()> {
+        let redactor = Redactor::builder().build()?;
+        let err = redactor.redact_bytes(b"", "odt");
+        assert!(matches!(&err, Err(RafError::UnsupportedFormat(f)) if f == "odt"));
+        assert_eq!(err.unwrap_err().to_string(), "unsupported format `odt`");
+        Ok(())
+    }
+
+    #[test]
+    fn test_pattern_names_its_generator() -> Result<()> {
+        let fin = |generator: &str| CompiledPattern {
+            types: vec!["fin".to_owned()],
+            generator: Some(generator.to_owned()),
+            ..CompiledPattern::new(Regex::new(r"[FG]\d{7}[A-Z]").unwrap())
+        };
+        let redactor = Redactor::builder()
+            .compiled_patterns([fin("nric")])
+            .strategy(Strategy::Fake)
+            .build()?;
+        let (output, _) = redactor.redact_text("G1234567X")?;
+        assert_ne!(output, "G1234567X");
+        assert!(Validator::Nric.is_valid(&output), "{output}");
+
+        let err = Redactor::builder()
+            .compiled_patterns([fin("passport")])
+            .build();
+        assert!(matches!(err, Err(RafError::UnknownGenerator { .. })));
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+//! Findings reports of `raf scan`: what redacting a set of files would
+//! replace, without writing anything.
+//!
+//! Reports never hold the matched values themselves, only masked as with
+//! [`Strategy::Mask`](crate::Strategy::Mask).
+
+use crate::error::{RafError, Result};
+use crate::mapping::{Location, RedactedData};
+use crate::matcher::Matcher;
+use crate::sarif;
+use crate::strategy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Format a [`ScanReport`] is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// An aligned text table, for terminals.
+    #[default]
+    Table,
+    Json,
+    /// One row per type found in each file, and one per file that could not
+    /// be scanned.
+    Csv,
+    /// SARIF 2.1.0, for code review tools; every finding is listed, under
+    /// the rule of its pattern.
+    Sarif,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(ReportFormat::Table),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            "sarif" => Ok(ReportFormat::Sarif),
+            _ => Err(format!(
+                "unknown report format `{s}`, expected `table`, `json`, `csv` or `sarif`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReportFormat::Table => "table",
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Sarif => "sarif",
+        })
+    }
+}
+
+/// A match found by a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub type_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern_id: Option<String>,
+    /// The matched value, masked.
+    pub masked: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl From<&RedactedData> for Finding {
+    fn from(data: &RedactedData) -> Self {
+        Finding {
+            type_name: data.type_name.clone().unwrap_or_default(),
+            pattern_id: data.pattern_id.clone(),
+            masked: strategy::mask(
+                &data.unredacted_text,
+                data.type_name.as_deref().unwrap_or_default(),
+            ),
+            location: data.location.clone(),
+        }
+    }
+}
+
+/// The findings of one file.
+#[derive(Debug, Clone, Default)]
+pub struct FileFindings {
+    pub path: PathBuf,
+    /// Number of findings of each type.
+    pub counts: BTreeMap<String, usize>,
+    /// Every finding, in document order.
+    pub findings: Vec<Finding>,
+}
+
+impl FileFindings {
+    /// The first `limit` findings of type `type_name`.
+    fn samples<'a>(
+        &'a self,
+        type_name: &'a str,
+        limit: usize,
+    ) -> impl Iterator<Item = &'a Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.type_name == type_name)
+            .take(limit)
+    }
+}
+
+/// A file that could not be scanned.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// A pattern the findings of a scan refer to by id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRule {
+    /// The `pattern_id` of the findings of the pattern.
+    pub id: String,
+    pub types: Vec<String>,
+    pub description: Option<String>,
+    /// The pattern's regex.
+    pub pattern: String,
+}
+
+/// What a scan found, file by file.
+#[derive(Debug, Clone, Default)]
+pub struct ScanReport {
+    /// The patterns scanned for, see [`ScanReport::with_patterns`].
+    pub rules: Vec<ScanRule>,
+    pub files: Vec<FileFindings>,
+    pub failures: Vec<ScanFailure>,
+}
+
+impl ScanReport {
+    pub fn new() -> Self {
+        ScanReport::default()
+    }
+
+    /// A report on the matches of the patterns of `matcher`, which SARIF
+    /// reports describe as rules.
+    pub fn with_patterns(matcher: &Matcher) -> Self {
+        let rules = matcher
+            .patterns()
+            .iter()
+            .enumerate()
+            .map(|(index, pattern)| ScanRule {
+                id: matcher.pattern_id(index),
+                types: pattern.types.clone(),
+                description: pattern.description.clone(),
+                pattern: pattern.finder.to_string(),
+            })
+            .collect();
+        ScanReport {
+            rules,
+            ..ScanReport::default()
+        }
+    }
+
+    /// Adds the result of [`Redactor::scan_file`](crate::Redactor::scan_file)
+    /// for the file at `path`.
+    pub fn add(&mut self, path: &Path, result: Result<Vec<RedactedData>>) {
+        match result {
+            Ok(all_redacted_data) => {
+                let findings: Vec<Finding> = all_redacted_data.iter().map(Finding::from).collect();
+                let mut counts = BTreeMap::new();
+                for finding in &findings {
+                    *counts.entry(finding.type_name.clone()).or_default() += 1;
+                }
+                self.files.push(FileFindings {
+                    path: path.to_path_buf(),
+                    counts,
+                    findings,
+                });
+            }
+            Err(error) => self.add_failure(path, &error),
+        }
+    }
+
+    /// Records that `path` could not be scanned.
+    pub fn add_failure(&mut self, path: &Path, error: &RafError) {
+        self.failures.push(ScanFailure {
+            path: path.to_path_buf(),
+            error: error.to_string(),
+        });
+    }
+
+    /// Number of findings of each type over all files.
+    pub fn totals(&self) -> BTreeMap<&str, usize> {
+        let mut totals = BTreeMap::new();
+        for (type_name, count) in self.files.iter().flat_map(|file| &file.counts) {
+            *totals.entry(type_name.as_str()).or_default() += count;
+        }
+        totals
+    }
+
+    /// Number of findings over all files.
+    pub fn total(&self) -> usize {
+        self.files.iter().map(|file| file.findings.len()).sum()
+    }
+
+    /// Writes the report in `format` to `writer`, with the locations of up to
+    /// `samples` findings of each type in each file, or of all findings in
+    /// SARIF.
+    pub fn write(
+        &self,
+        format: ReportFormat,
+        samples: usize,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        match format {
+            ReportFormat::Table => self.write_table(samples, writer),
+            ReportFormat::Json => self.write_json(samples, writer),
+            ReportFormat::Csv => self.write_csv(samples, writer),
+            ReportFormat::Sarif => sarif::write(self, writer),
+        }
+        .map_err(RafError::Stream)
+    }
+
+    fn write_table(&self, samples: usize, writer: &mut dyn Write) -> io::Result<()> {
+        let mut rows = vec![[
+            "FILE".to_owned(),
+            "TYPE".to_owned(),
+            "COUNT".to_owned(),
+            "SAMPLES".to_owned(),
+        ]];
+        for file in &self.files {
+            let path = file.path.display().to_string();
+            if file.counts.is_empty() {
+                rows.push([path.clone(), "-".to_owned(), "0".to_owned(), String::new()]);
+            }
+            for (type_name, count) in &file.counts {
+                rows.push([
+                    path.clone(),
+                    type_name.clone(),
+                    count.to_string(),
+                    describe_samples(file, type_name, samples, ", "),
+                ]);
+            }
+        }
+        for (type_name, count) in self.totals() {
+            rows.push([
+                "TOTAL".to_owned(),
+                type_name.to_owned(),
+                count.to_string(),
+                String::new(),
+            ]);
+        }
+
+        let width = |column: usize| rows.iter().map(|row| row[column].chars().count()).max();
+        let widths = [0, 1, 2].map(|column| width(column).unwrap_or_default());
+        for [path, type_name, count, samples] in &rows {
+            let line = format!(
+                "{path:<0$}  {type_name:<1$}  {count:>2$}  {samples}",
+                widths[0], widths[1], widths[2]
+            );
+            writeln!(writer, "{}", line.trim_end())?;
+        }
+
+        if !self.failures.is_empty() {
+            writeln!(writer, "\nCould not scan {} file(s):", self.failures.len())?;
+            for failure in &self.failures {
+                writeln!(writer, "  {}: {}", failure.path.display(), failure.error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_json(&self, samples: usize, writer: &mut dyn Write) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct JsonReport<'a> {
+            totals: BTreeMap<&'a str, usize>,
+            files: Vec<JsonFile<'a>>,
+            failures: &'a [ScanFailure],
+        }
+        #[derive(Serialize)]
+        struct JsonFile<'a> {
+            path: &'a Path,
+            counts: &'a BTreeMap<String, usize>,
+            samples: Vec<&'a Finding>,
+        }
+
+        let files = self
+            .files
+            .iter()
+            .map(|file| JsonFile {
+                path: &file.path,
+                counts: &file.counts,
+                samples: file
+                    .counts
+                    .keys()
+                    .flat_map(|type_name| file.samples(type_name, samples))
+                    .collect(),
+            })
+            .collect();
+        let report = JsonReport {
+            totals: self.totals(),
+            files,
+            failures: &self.failures,
+        };
+        serde_json::to_writer_pretty(&mut *writer, &report)?;
+        writeln!(writer)
+    }
+
+    fn write_csv(&self, samples: usize, writer: &mut dyn Write) -> io::Result<()> {
+        let mut csv = csv::Writer::from_writer(writer);
+        csv.write_record(["file", "type", "count", "samples", "error"])?;
+        for file in &self.files {
+            let path = file.path.display().to_string();
+            if file.counts.is_empty() {
+                csv.write_record([path.as_str(), "", "0", "", ""])?;
+            }
+            for (type_name, count) in &file.counts {
+                csv.write_record([
+                    path.as_str(),
+                    type_name,
+                    &count.to_string(),
+                    &describe_samples(file, type_name, samples, "; "),
+                    "",
+                ])?;
+            }
+        }
+        for failure in &self.failures {
+            let path = failure.path.display().to_string();
+            csv.write_record([path.as_str(), "", "", "", &failure.error])?;
+        }
+        csv.flush()
+    }
+}
+
+/// The locations and masked values of the first `limit` findings of
+/// `type_name` in `file`, separated by `separator`.
+fn describe_samples(file: &FileFindings, type_name: &str, limit: usize, separator: &str) -> String {
+    file.samples(type_name, limit)
+        .map(|finding| match &finding.location {
+            Some(location) => format!("{location} {}", finding.masked),
+            None => finding.masked.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Redactor;
+
+    #[test]
+    fn test_reports_masked_findings() -> Result<()> {
+        let redactor = Redactor::builder().patterns([r"\d{8}"]).build()?;
+        let (_, mapping) = redactor.redact_text("call 91234567\nor 87654321")?;
+        let mut report = ScanReport::new();
+        report.add(Path::new("a.txt"), Ok(mapping));
+        report.add(
+            Path::new("b.bin"),
+            Err(RafError::UnsupportedFormat("bin".to_owned())),
+        );
+        assert_eq!(report.total(), 2);
+
+        let mut table = Vec::new();
+        report.write(ReportFormat::Table, 1, &mut table)?;
+        let table = String::from_utf8(table).unwrap();
+        assert!(
+            table.contains("a.txt  redacted      2  1:6 9*******"),
+            "{table}"
+        );
+        assert!(!table.contains("87654321"));
+        assert!(table.contains("b.bin: unsupported format `bin`"));
+
+        let mut csv = Vec::new();
+        report.write(ReportFormat::Csv, 3, &mut csv)?;
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(
+            csv.contains("a.txt,redacted,2,1:6 9*******; 2:4 8*******,"),
+            "{csv}"
+        );
+        assert!(csv.contains("b.bin,,,,unsupported format `bin`"));
+        Ok(())
+    }
+}
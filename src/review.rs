@@ -0,0 +1,285 @@
+//! A self-contained HTML report of a redaction run, to review each
+//! redaction in context without opening the original and redacted files
+//! side by side.
+//!
+//! The context comes from [`RedactedData::context`], so the [`Redactor`]
+//! must be built with [`RedactorBuilder::context`].
+//!
+//! [`Redactor`]: crate::Redactor
+//! [`RedactorBuilder::context`]: crate::RedactorBuilder::context
+
+use crate::error::{RafError, Result};
+use crate::mapping::RedactedData;
+use crate::report::ScanFailure;
+use crate::strategy;
+use crate::utils;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Characters of context recorded around each match for a review report.
+pub const DEFAULT_CONTEXT: usize = 40;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f3f3f3; }
+td.snippet { font-family: monospace; white-space: pre-wrap; max-width: 40em; }
+mark.original { background: #fdd; }
+mark.replacement { background: #dfd; }
+.warning { color: #a00; font-weight: bold; }
+";
+
+/// The redactions of one file.
+#[derive(Debug, Clone, Default)]
+pub struct ReviewedFile {
+    pub path: PathBuf,
+    pub records: Vec<RedactedData>,
+}
+
+/// What a run redacted, file by file, rendered as HTML by
+/// [`ReviewReport::write_html`].
+#[derive(Debug, Clone, Default)]
+pub struct ReviewReport {
+    pub files: Vec<ReviewedFile>,
+    pub failures: Vec<ScanFailure>,
+    /// Whether original values are masked, so the report can be shared.
+    pub mask_originals: bool,
+}
+
+impl ReviewReport {
+    pub fn new(mask_originals: bool) -> Self {
+        ReviewReport {
+            mask_originals,
+            ..ReviewReport::default()
+        }
+    }
+
+    /// Adds the result of redacting the file at `path`.
+    pub fn add(&mut self, path: &Path, result: &Result<Vec<RedactedData>>) {
+        match result {
+            Ok(records) => self.files.push(ReviewedFile {
+                path: path.to_path_buf(),
+                records: records.clone(),
+            }),
+            Err(error) => self.failures.push(ScanFailure {
+                path: path.to_path_buf(),
+                error: error.to_string(),
+            }),
+        }
+    }
+
+    /// Writes the report as a single HTML page to `writer`.
+    pub fn write_html(&self, writer: &mut dyn Write) -> Result<()> {
+        writer
+            .write_all(self.render().as_bytes())
+            .map_err(RafError::Stream)
+    }
+
+    /// Writes the report to `path`, readable by its owner only, as it may
+    /// hold the original values.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        utils::write_private(path, self.render().as_bytes())
+    }
+
+    fn render(&self) -> String {
+        let mut html = String::new();
+        // Writing to a `String` cannot fail.
+        let _ = self.render_into(&mut html);
+        html
+    }
+
+    fn render_into(&self, html: &mut String) -> std::fmt::Result {
+        let total: usize = self.files.iter().map(|file| file.records.len()).sum();
+        writeln!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>raf redaction review</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+             <h1>raf redaction review</h1>"
+        )?;
+        if self.mask_originals {
+            writeln!(html, "<p>Original values are masked.</p>")?;
+        } else {
+            writeln!(
+                html,
+                "<p class=\"warning\">This report contains the original values. \
+                 Do not share it.</p>"
+            )?;
+        }
+
+        writeln!(html, "<h2>Summary</h2>\n<table>")?;
+        writeln!(
+            html,
+            "<tr><th>Files redacted</th><td>{}</td></tr>",
+            self.files.len()
+        )?;
+        writeln!(
+            html,
+            "<tr><th>Files with errors</th><td>{}</td></tr>",
+            self.failures.len()
+        )?;
+        writeln!(
+            html,
+            "<tr><th>Redactions</th><td>{total}</td></tr>\n</table>"
+        )?;
+        let mut types: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for file in &self.files {
+            let mut seen = Vec::new();
+            for data in &file.records {
+                let type_name = data.type_name.as_deref().unwrap_or_default();
+                let (count, files) = types.entry(type_name).or_default();
+                *count += 1;
+                if !seen.contains(&type_name) {
+                    seen.push(type_name);
+                    *files += 1;
+                }
+            }
+        }
+        if !types.is_empty() {
+            writeln!(
+                html,
+                "<table>\n<tr><th>Type</th><th>Redactions</th><th>Files</th></tr>"
+            )?;
+            for (type_name, (count, files)) in types {
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{count}</td><td>{files}</td></tr>",
+                    escape(type_name)
+                )?;
+            }
+            writeln!(html, "</table>")?;
+        }
+
+        if !self.failures.is_empty() {
+            writeln!(html, "<h2>Errors</h2>\n<ul>")?;
+            for failure in &self.failures {
+                writeln!(
+                    html,
+                    "<li><code>{}</code>: {}</li>",
+                    escape(&failure.path.display().to_string()),
+                    escape(&failure.error)
+                )?;
+            }
+            writeln!(html, "</ul>")?;
+        }
+
+        writeln!(html, "<h2>Files</h2>")?;
+        for file in &self.files {
+            writeln!(
+                html,
+                "<h3><code>{}</code>, {} redaction(s)</h3>",
+                escape(&file.path.display().to_string()),
+                file.records.len()
+            )?;
+            if file.records.is_empty() {
+                continue;
+            }
+            writeln!(
+                html,
+                "<table>\n<tr><th>#</th><th>Type</th><th>Pattern</th><th>Location</th>\
+                 <th>Before</th><th>After</th></tr>"
+            )?;
+            for (idx, data) in file.records.iter().enumerate() {
+                let (before, after) = self.snippets(data);
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                     <td class=\"snippet\">{before}</td><td class=\"snippet\">{after}</td></tr>",
+                    idx + 1,
+                    escape(data.type_name.as_deref().unwrap_or_default()),
+                    escape(data.pattern_id.as_deref().unwrap_or_default()),
+                    data.location
+                        .as_ref()
+                        .map(|location| escape(&location.to_string()))
+                        .unwrap_or_default(),
+                )?;
+            }
+            writeln!(html, "</table>")?;
+        }
+        writeln!(html, "</body>\n</html>")
+    }
+
+    /// The HTML of the text around `data` before and after redaction. When
+    /// originals are masked, the text around them is the redacted one, so
+    /// that neighbouring matches do not show either.
+    fn snippets(&self, data: &RedactedData) -> (String, String) {
+        let original = |value: &str| format!("<mark class=\"original\">{}</mark>", escape(value));
+        let replacement =
+            |value: &str| format!("<mark class=\"replacement\">{}</mark>", escape(value));
+        let value = match self.mask_originals {
+            true => strategy::mask(
+                &data.unredacted_text,
+                data.type_name.as_deref().unwrap_or_default(),
+            ),
+            false => data.unredacted_text.clone(),
+        };
+        let Some(context) = &data.context else {
+            return (original(&value), replacement(&data.redacted_text));
+        };
+        let (before, after) = match self.mask_originals {
+            true => (&context.redacted_before, &context.redacted_after),
+            false => (&context.before, &context.after),
+        };
+        (
+            format!("{}{}{}", escape(before), original(&value), escape(after)),
+            format!(
+                "{}{}{}",
+                escape(&context.redacted_before),
+                replacement(&context.replacement),
+                escape(&context.redacted_after)
+            ),
+        )
+    }
+}
+
+/// `text` escaped for HTML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Redactor, Strategy};
+
+    #[test]
+    fn test_html_shows_context() -> Result<()> {
+        let redactor = Redactor::builder()
+            .patterns([r"\d{8}"])
+            .strategy(Strategy::Counter)
+            .context(8)
+            .build()?;
+        let result = redactor
+            .redact_text("call 91234567 or 87654321 now")
+            .map(|(_, data)| data);
+        let mut report = ReviewReport::new(false);
+        report.add(Path::new("a.txt"), &result);
+
+        let mut html = Vec::new();
+        report.write_html(&mut html)?;
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("call <mark class=\"original\">91234567</mark> or 8765"));
+        assert!(
+            html.contains("call <mark class=\"replacement\">&lt;REDACTED_1&gt;</mark> or &lt;RED")
+        );
+
+        report.mask_originals = true;
+        let masked = report.render();
+        assert!(!masked.contains("91234567") && !masked.contains("87654321"));
+        assert!(masked.contains("call <mark class=\"original\">9*******</mark> or &lt;RED"));
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+//! SARIF 2.1.0 output of a [`ScanReport`], see
+//! <https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html>.
+//!
+//! Each pattern is a rule and each finding a result. Matches in plain text
+//! are located by line and column; matches in a part of a document, such as
+//! a `.docx` run, only by file, with the part named in the message.
+
+use crate::report::{Finding, ScanReport, ScanRule};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+pub(crate) fn write(report: &ScanReport, writer: &mut dyn Write) -> io::Result<()> {
+    let mut rules: Vec<ScanRule> = report.rules.clone();
+    // Reports built without patterns only know their rules from findings.
+    for finding in report.files.iter().flat_map(|file| &file.findings) {
+        let id = finding.pattern_id.clone().unwrap_or_default();
+        if !rules.iter().any(|rule| rule.id == id) {
+            rules.push(ScanRule {
+                id,
+                types: vec![finding.type_name.clone()],
+                description: None,
+                pattern: String::new(),
+            });
+        }
+    }
+
+    let results: Vec<Value> = report
+        .files
+        .iter()
+        .flat_map(|file| {
+            let rules = &rules;
+            file.findings
+                .iter()
+                .map(move |finding| result(finding, &file.path, rules))
+        })
+        .collect();
+    let notifications: Vec<Value> = report
+        .failures
+        .iter()
+        .map(|failure| {
+            json!({
+                "level": "error",
+                "message": { "text": failure.error },
+                "locations": [{ "physicalLocation": artifact(&failure.path) }],
+            })
+        })
+        .collect();
+
+    let sarif = json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "raf",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(rule).collect::<Vec<_>>(),
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "invocations": [{
+                "executionSuccessful": true,
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(&mut *writer, &sarif)?;
+    writeln!(writer)
+}
+
+fn rule(rule: &ScanRule) -> Value {
+    let type_name = rule.types.first().map_or("redacted", String::as_str);
+    let mut full = format!("Text matching the `{}` pattern", rule.types.join("`, `"));
+    if !rule.pattern.is_empty() {
+        full.push_str(&format!(" `{}`", rule.pattern));
+    }
+    let short = match &rule.description {
+        Some(description) => description.clone(),
+        None => format!("Possible {type_name}"),
+    };
+    json!({
+        "id": rule.id,
+        "shortDescription": { "text": short },
+        "fullDescription": { "text": full },
+        "defaultConfiguration": { "level": "warning" },
+        "properties": { "tags": rule.types },
+    })
+}
+
+fn result(finding: &Finding, path: &Path, rules: &[ScanRule]) -> Value {
+    let rule_id = finding.pattern_id.clone().unwrap_or_default();
+    let rule_index = rules.iter().position(|rule| rule.id == rule_id);
+    let mut text = format!("Possible {} `{}`", finding.type_name, finding.masked);
+    let mut location = artifact(path);
+    match &finding.location {
+        Some(found) if found.part.is_some() => text.push_str(&format!(" at {found}")),
+        Some(found) => {
+            location["region"] = json!({
+                "startLine": found.line,
+                "startColumn": found.column,
+                "charOffset": found.char_start,
+                "charLength": found.char_end - found.char_start,
+            });
+        }
+        None => {}
+    }
+    json!({
+        "ruleId": rule_id,
+        "ruleIndex": rule_index,
+        "level": "warning",
+        "message": { "text": text },
+        "locations": [{ "physicalLocation": location }],
+    })
+}
+
+/// A `physicalLocation` naming only the file at `path`.
+fn artifact(path: &Path) -> Value {
+    json!({ "artifactLocation": { "uri": uri(path) } })
+}
+
+/// `path` as a URI reference: relative paths stay relative, with `/`
+/// separators, and absolute ones become `file://` URIs.
+fn uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::with_capacity(path.len());
+    if path.starts_with('/') {
+        uri.push_str("file://");
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        // A Windows drive letter.
+        uri.push_str("file:///");
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ReportFormat;
+    use crate::Redactor;
+
+    #[test]
+    fn test_sarif_results() -> crate::Result<()> {
+        let redactor = Redactor::builder().patterns([r"\d{8}"]).build()?;
+        let (_, mapping) = redactor.redact_text("call\nme on 91234567")?;
+        let mut report = ScanReport::with_patterns(redactor.matcher());
+        report.add(Path::new("my notes/a.txt"), Ok(mapping));
+
+        let mut sarif = Vec::new();
+        report.write(ReportFormat::Sarif, 0, &mut sarif)?;
+        let sarif: Value = serde_json::from_slice(&sarif).unwrap();
+        let run = &sarif["runs"][0];
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "0");
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "0");
+        assert_eq!(result["message"]["text"], "Possible redacted `9*******`");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "my%20notes/a.txt");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 7);
+        Ok(())
+    }
+}
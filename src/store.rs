@@ -0,0 +1,316 @@
+//! A mapping store shared by all the files of a run.
+//!
+//! Instead of one `-unredact.json` per file, the store is a single JSON Lines
+//! file that is only ever appended to. Each original value is written once,
+//! as a `value` line with a numeric id; every place it was redacted is an
+//! `occurrence` line pointing to that id, with the token and the file and
+//! location it was found in. Reopening a store in a later run keeps the ids
+//! of the values already in it.
+
+use crate::error::{RafError, Result};
+use crate::mapping::{Location, RedactedData};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// One line of the store.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Line {
+    Value(StoredValue),
+    Occurrence(Occurrence),
+}
+
+/// An original value, stored once however many times it was redacted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StoredValue {
+    pub id: u64,
+    pub unredacted_text: String,
+    /// Type of the first match of the value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+}
+
+/// One place a value was redacted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Occurrence {
+    /// Id of the [`StoredValue`].
+    pub value: u64,
+    pub redacted_text: String,
+    /// The input file, `-` for stdin.
+    pub file: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+/// Appends the mappings of many files to one store, see the
+/// [module documentation](self).
+pub struct MappingStore {
+    path: PathBuf,
+    state: Mutex<StoreState>,
+}
+
+struct StoreState {
+    writer: BufWriter<fs::File>,
+    ids: HashMap<String, u64>,
+    next_id: u64,
+}
+
+impl MappingStore {
+    /// Opens the store at `path`, creating it readable by its owner only if
+    /// it does not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let index = if path.exists() {
+            MappingIndex::load(&path)?
+        } else {
+            MappingIndex::default()
+        };
+        let ids: HashMap<String, u64> = index
+            .values
+            .values()
+            .map(|value| (value.unredacted_text.clone(), value.id))
+            .collect();
+        let next_id = index.values.keys().max().map_or(0, |id| id + 1);
+
+        let mut options = fs::OpenOptions::new();
+        options.append(true).create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(&path).map_err(|source| RafError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(MappingStore {
+            path,
+            state: Mutex::new(StoreState {
+                writer: BufWriter::new(file),
+                ids,
+                next_id,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the mapping of the input `file` to the store.
+    pub fn append(&self, file: &Path, all_redacted_data: &[RedactedData]) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let state = &mut *state;
+        let mut lines = Vec::new();
+        for data in all_redacted_data {
+            let value = match state.ids.get(&data.unredacted_text) {
+                Some(id) => *id,
+                None => {
+                    let id = state.next_id;
+                    state.next_id += 1;
+                    state.ids.insert(data.unredacted_text.clone(), id);
+                    lines.push(Line::Value(StoredValue {
+                        id,
+                        unredacted_text: data.unredacted_text.clone(),
+                        type_name: data.type_name.clone(),
+                    }));
+                    id
+                }
+            };
+            lines.push(Line::Occurrence(Occurrence {
+                value,
+                redacted_text: data.redacted_text.clone(),
+                file: file.to_path_buf(),
+                pattern_id: data.pattern_id.clone(),
+                location: data.location.clone(),
+            }));
+        }
+
+        let io_error = |source| RafError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        for line in &lines {
+            serde_json::to_writer(&mut state.writer, line).map_err(|source| {
+                RafError::MappingWrite {
+                    path: self.path.clone(),
+                    source,
+                }
+            })?;
+            state.writer.write_all(b"\n").map_err(io_error)?;
+        }
+        // Flush per file, so an interrupted run keeps the files it finished.
+        state.writer.flush().map_err(io_error)
+    }
+}
+
+impl fmt::Debug for MappingStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappingStore")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A store loaded in memory, to look values up by token or tokens by value.
+#[derive(Debug, Default)]
+pub struct MappingIndex {
+    values: HashMap<u64, StoredValue>,
+    occurrences: Vec<Occurrence>,
+}
+
+impl MappingIndex {
+    /// Reads the store at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let io_error = |source| RafError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = fs::File::open(path).map_err(io_error)?;
+        let mut index = MappingIndex::default();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(io_error)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line = serde_json::from_str(&line).map_err(|source| RafError::MappingRead {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            match line {
+                Line::Value(value) => {
+                    index.values.insert(value.id, value);
+                }
+                Line::Occurrence(occurrence) => index.occurrences.push(occurrence),
+            }
+        }
+        Ok(index)
+    }
+
+    /// The original value a token was redacted from, or
+    /// [`RafError::AmbiguousToken`] if the token stands for several values.
+    pub fn value_of_token(&self, token: &str) -> Result<Option<&StoredValue>> {
+        let mut ids: Vec<u64> = self
+            .occurrences
+            .iter()
+            .filter(|occurrence| occurrence.redacted_text == token)
+            .map(|occurrence| occurrence.value)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        match ids[..] {
+            [] => Ok(None),
+            [id] => Ok(self.values.get(&id)),
+            _ => Err(RafError::AmbiguousToken {
+                token: token.to_owned(),
+                count: ids.len(),
+            }),
+        }
+    }
+
+    /// The stored value equal to `unredacted_text`.
+    pub fn value(&self, unredacted_text: &str) -> Option<&StoredValue> {
+        self.values
+            .values()
+            .find(|value| value.unredacted_text == unredacted_text)
+    }
+
+    /// The input files recorded in the store, each once, in the order they
+    /// were stored.
+    pub fn files(&self) -> Vec<&Path> {
+        let mut files: Vec<&Path> = Vec::new();
+        for occurrence in &self.occurrences {
+            if !files.contains(&occurrence.file.as_path()) {
+                files.push(&occurrence.file);
+            }
+        }
+        files
+    }
+
+    /// The mapping of the input `file`, as it would have been written to its
+    /// own `-unredact.json`.
+    pub fn mapping_of(&self, file: &Path) -> Vec<RedactedData> {
+        self.occurrences
+            .iter()
+            .filter(|occurrence| occurrence.file == file)
+            .filter_map(|occurrence| {
+                let value = self.values.get(&occurrence.value)?;
+                Some(RedactedData {
+                    unredacted_text: value.unredacted_text.clone(),
+                    redacted_text: occurrence.redacted_text.clone(),
+                    pattern_id: occurrence.pattern_id.clone(),
+                    type_name: value.type_name.clone(),
+                    location: occurrence.location.clone(),
+                    context: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Every place the value with id `value` was redacted, in the order they
+    /// were stored.
+    pub fn occurrences(&self, value: u64) -> impl Iterator<Item = &Occurrence> {
+        self.occurrences
+            .iter()
+            .filter(move |occurrence| occurrence.value == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(unredacted_text: &str, redacted_text: &str) -> RedactedData {
+        RedactedData {
+            unredacted_text: unredacted_text.to_owned(),
+            redacted_text: redacted_text.to_owned(),
+            ..RedactedData::default()
+        }
+    }
+
+    #[test]
+    fn test_dedup_and_lookups() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("raf-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mappings.jsonl");
+
+        let store = MappingStore::open(&path)?;
+        store.append(
+            Path::new("a.txt"),
+            &[data("Tan", "tok1"), data("Lim", "tok2")],
+        )?;
+        store.append(Path::new("b.txt"), &[data("Tan", "tok3")])?;
+        drop(store);
+        // A later run keeps the ids of the values already stored.
+        MappingStore::open(&path)?.append(Path::new("c.txt"), &[data("Tan", "tok4")])?;
+
+        let index = MappingIndex::load(&path)?;
+        let tan = index.value("Tan").unwrap();
+        assert_eq!(index.value_of_token("tok3")?, Some(tan));
+        assert_eq!(index.value_of_token("tok5")?, None);
+        let files: Vec<_> = index.occurrences(tan.id).map(|o| o.file.clone()).collect();
+        assert_eq!(files, ["a.txt", "b.txt", "c.txt"].map(PathBuf::from));
+        assert_eq!(index.files().len(), 3);
+        let mapping = index.mapping_of(Path::new("a.txt"));
+        assert_eq!(mapping, [data("Tan", "tok1"), data("Lim", "tok2")]);
+        let values = fs::read_to_string(&path).unwrap();
+        assert_eq!(values.matches(r#""kind":"value""#).count(), 2);
+
+        // Label tokens are shared by every value of a type.
+        MappingStore::open(&path)?.append(
+            Path::new("d.txt"),
+            &[data("Tan", "[PERSON]"), data("Lim", "[PERSON]")],
+        )?;
+        let index = MappingIndex::load(&path)?;
+        assert!(matches!(
+            index.value_of_token("[PERSON]"),
+            Err(RafError::AmbiguousToken { count: 2, .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+}
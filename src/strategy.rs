@@ -0,0 +1,398 @@
+use crate::error::RafError;
+use crate::fake::GeneratorRegistry;
+use crate::ff1::Ff1Cipher;
+use crate::utils;
+use hmac::{Hmac, Mac};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// How a matched piece of text is replaced in the redacted output.
+///
+/// Strategies are written on the command line and in `patterns.json` as
+/// `random`, `hmac[:N]`, `fake`, `fpe`, `label[:TEXT]`, `mask`, `keep-last:N`,
+/// `blackout[:N]`, `counter` or `remove`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Strategy {
+    /// `[REDACTED:<token>]`, where `<token>` is a random alphanumeric string
+    /// as long as the matched text.
+    #[default]
+    Random,
+    /// `[REDACTED:<token>]`, where `<token>` is the first `N` hex digits of
+    /// the HMAC-SHA256 of the matched text under the redactor's key, so equal
+    /// values get equal tokens in every file and every run with that key.
+    Hmac(usize),
+    /// A fake value of the same type and shape, e.g. `qhzt@example.com`, made
+    /// by the [`FakeGenerator`](crate::fake::FakeGenerator) of the pattern's
+    /// type. A value gets the same fake value everywhere in a run, and across
+    /// runs when the redactor has a key.
+    Fake,
+    /// The match encrypted under the redactor's key with format-preserving
+    /// encryption: digits stay digits and letters stay letters, e.g.
+    /// `S1234567D` becomes `M7990119Z`. Tokens can be decrypted with
+    /// [`Redactor::decrypt`](crate::Redactor::decrypt), without a mapping.
+    /// Values with fewer than 6 digits and fewer than 5 letters are too short
+    /// to encrypt and fail the redaction.
+    Fpe,
+    /// `[TEXT]`, or the upper-cased type name of the pattern, e.g. `[EMAIL]`.
+    Label(Option<String>),
+    /// Replaces letters and digits with `*`, keeping the first character,
+    /// punctuation and, for emails, the top-level domain, e.g.
+    /// `j***@****.com` or `1**.***.*.***`.
+    Mask,
+    /// Replaces letters and digits with `*` except for the last `N` of them,
+    /// keeping punctuation, e.g. `****-1234`.
+    KeepLast(usize),
+    /// `N` block characters, whatever the length of the match, e.g. `█████`.
+    Blackout(usize),
+    /// `<TYPE_N>`, numbering the distinct values of each type in the order
+    /// they are first seen, e.g. `<PERSON_3>`.
+    Counter,
+    /// Removes the match altogether.
+    Remove,
+}
+
+const DEFAULT_BLACKOUT_LEN: usize = 5;
+const DEFAULT_HMAC_LEN: usize = 16;
+/// Hex digits in an HMAC-SHA256.
+const MAX_HMAC_LEN: usize = 64;
+
+impl Strategy {
+    /// Returns the text to splice into the document in place of `matched`,
+    /// together with the token recorded in the unredact mapping. Fails if
+    /// `matched` is too short for [`Strategy::Fpe`].
+    pub(crate) fn replace(
+        &self,
+        matched: &str,
+        type_name: &str,
+        state: &StrategyState,
+    ) -> crate::Result<(String, String)> {
+        let replacement = match self {
+            Strategy::Random => {
+                let token = utils::randomize_string(matched);
+                (format!("[REDACTED:{token}]"), token)
+            }
+            Strategy::Hmac(len) => {
+                let token = state.hmac_token(matched, *len);
+                (format!("[REDACTED:{token}]"), token)
+            }
+            Strategy::Fake => {
+                let fake = state.fake(type_name, matched);
+                (fake.clone(), fake)
+            }
+            Strategy::Fpe => match state.encrypt(matched) {
+                Some(encrypted) => (encrypted.clone(), encrypted),
+                None => {
+                    return Err(RafError::FpeTooShort {
+                        type_name: type_name.to_owned(),
+                    })
+                }
+            },
+            Strategy::Label(label) => {
+                let label = label.clone().unwrap_or_else(|| type_name.to_uppercase());
+                let replacement = format!("[{label}]");
+                (replacement.clone(), replacement)
+            }
+            Strategy::Mask => {
+                let masked = mask(matched, type_name);
+                (masked.clone(), masked)
+            }
+            Strategy::KeepLast(keep) => {
+                let total = matched.chars().filter(|c| c.is_alphanumeric()).count();
+                let mut seen = 0;
+                let masked: String = matched
+                    .chars()
+                    .map(|c| {
+                        if !c.is_alphanumeric() {
+                            return c;
+                        }
+                        seen += 1;
+                        if seen + keep > total {
+                            c
+                        } else {
+                            '*'
+                        }
+                    })
+                    .collect();
+                (masked.clone(), masked)
+            }
+            Strategy::Blackout(len) => {
+                let blackout = "█".repeat(*len);
+                (blackout.clone(), blackout)
+            }
+            Strategy::Counter => {
+                let n = state.number(type_name, matched);
+                let replacement = format!("<{}_{n}>", type_name.to_uppercase());
+                (replacement.clone(), replacement)
+            }
+            Strategy::Remove => (String::new(), String::new()),
+        };
+        Ok(replacement)
+    }
+}
+
+/// Replaces letters and digits with `*` except for the first character and,
+/// for the `email` type only, the top-level domain, see [`Strategy::Mask`].
+/// Tokens, addresses and other values keep nothing else in clear.
+pub(crate) fn mask(matched: &str, type_name: &str) -> String {
+    let suffix_start = match type_name {
+        "email" | "emails" => matched
+            .rfind('.')
+            .filter(|&dot| matched[..dot].contains('@'))
+            .unwrap_or(matched.len()),
+        _ => matched.len(),
+    };
+    matched
+        .char_indices()
+        .map(|(idx, c)| {
+            if idx == 0 || idx >= suffix_start || !c.is_alphanumeric() {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let parse_len = |arg: &str| {
+            arg.parse::<usize>()
+                .map_err(|err| format!("invalid length `{arg}` in strategy `{s}`, {err}"))
+        };
+        match (name, arg) {
+            ("random", None) => Ok(Strategy::Random),
+            ("hmac", None) => Ok(Strategy::Hmac(DEFAULT_HMAC_LEN)),
+            ("hmac", Some(arg)) => match parse_len(arg)? {
+                len @ 1..=MAX_HMAC_LEN => Ok(Strategy::Hmac(len)),
+                _ => Err(format!(
+                    "invalid length `{arg}` in strategy `{s}`, expected 1 to {MAX_HMAC_LEN}"
+                )),
+            },
+            ("fake", None) => Ok(Strategy::Fake),
+            ("fpe", None) => Ok(Strategy::Fpe),
+            ("label", arg) => Ok(Strategy::Label(arg.map(str::to_owned))),
+            ("mask", None) => Ok(Strategy::Mask),
+            ("keep-last", Some(arg)) => Ok(Strategy::KeepLast(parse_len(arg)?)),
+            ("blackout", None) => Ok(Strategy::Blackout(DEFAULT_BLACKOUT_LEN)),
+            ("blackout", Some(arg)) => Ok(Strategy::Blackout(parse_len(arg)?)),
+            ("counter", None) => Ok(Strategy::Counter),
+            ("remove", None) => Ok(Strategy::Remove),
+            _ => Err(format!(
+                "unknown strategy `{s}`, expected one of `random`, `hmac[:N]`, `fake`, `fpe`, \
+                 `label[:TEXT]`, `mask`, `keep-last:N`, `blackout[:N]`, `counter` or `remove`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Random => f.write_str("random"),
+            Strategy::Hmac(len) => write!(f, "hmac:{len}"),
+            Strategy::Fake => f.write_str("fake"),
+            Strategy::Fpe => f.write_str("fpe"),
+            Strategy::Label(None) => f.write_str("label"),
+            Strategy::Label(Some(label)) => write!(f, "label:{label}"),
+            Strategy::Mask => f.write_str("mask"),
+            Strategy::KeepLast(keep) => write!(f, "keep-last:{keep}"),
+            Strategy::Blackout(len) => write!(f, "blackout:{len}"),
+            Strategy::Counter => f.write_str("counter"),
+            Strategy::Remove => f.write_str("remove"),
+        }
+    }
+}
+
+/// What strategies share across all the files redacted by one
+/// [`Redactor`](crate::Redactor): the numbers handed out by
+/// [`Strategy::Counter`], the fake values of [`Strategy::Fake`] and the key.
+#[derive(Default)]
+pub(crate) struct StrategyState {
+    counters: Mutex<HashMap<String, HashMap<String, usize>>>,
+    fakes: Mutex<HashMap<(String, String), String>>,
+    generators: GeneratorRegistry,
+    key: Option<Vec<u8>>,
+    cipher: Option<Ff1Cipher>,
+}
+
+impl StrategyState {
+    pub(crate) fn new(key: Option<Vec<u8>>, generators: GeneratorRegistry) -> Self {
+        let mut state = StrategyState {
+            key,
+            generators,
+            ..StrategyState::default()
+        };
+        if state.key.is_some() {
+            state.cipher = Some(Ff1Cipher::new(&state.mac(&["raf-fpe-key"])));
+        }
+        state
+    }
+
+    /// HMAC-SHA256 of `parts` under the key, each part followed by a NUL.
+    ///
+    /// Every use passes its own label as the first part, so that no redacted
+    /// value can hash to the FF1 key or to another use's input.
+    fn mac(&self, parts: &[&str]) -> [u8; 32] {
+        // `RedactorBuilder::build` refuses keyed strategies without a key.
+        let key = self.key.as_deref().unwrap_or_default();
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        for part in parts {
+            mac.update(part.as_bytes());
+            mac.update(b"\0");
+        }
+        mac.finalize().into_bytes().into()
+    }
+
+    fn hmac_token(&self, value: &str, len: usize) -> String {
+        let hex: String = self
+            .mac(&["raf-hmac", value])
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        hex[..len.min(MAX_HMAC_LEN)].to_owned()
+    }
+
+    pub(crate) fn is_keyless(&self) -> bool {
+        self.key.is_none()
+    }
+
+    fn encrypt(&self, value: &str) -> Option<String> {
+        self.cipher.as_ref()?.encrypt(value)
+    }
+
+    /// Decrypts a token of [`Strategy::Fpe`], or returns `None` without a key
+    /// or when the token is too short to have been encrypted.
+    pub(crate) fn decrypt(&self, token: &str) -> Option<String> {
+        self.cipher.as_ref()?.decrypt(token)
+    }
+
+    fn fake(&self, type_name: &str, value: &str) -> String {
+        let mut fakes = self.fakes.lock().unwrap_or_else(|err| err.into_inner());
+        fakes
+            .entry((type_name.to_owned(), value.to_owned()))
+            .or_insert_with(|| {
+                let mut rng = match self.key {
+                    Some(_) => StdRng::from_seed(self.mac(&["raf-fake", type_name, value])),
+                    None => StdRng::from_entropy(),
+                };
+                self.generators.get(type_name).generate(value, &mut rng)
+            })
+            .clone()
+    }
+
+    fn number(&self, type_name: &str, value: &str) -> usize {
+        let mut counters = self.counters.lock().unwrap_or_else(|err| err.into_inner());
+        let numbers = counters.entry(type_name.to_owned()).or_default();
+        let next = numbers.len() + 1;
+        *numbers.entry(value.to_owned()).or_insert(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(strategy: &str, matched: &str, type_name: &str, state: &StrategyState) -> String {
+        let strategy: Strategy = strategy.parse().unwrap();
+        strategy.replace(matched, type_name, state).unwrap().0
+    }
+
+    #[test]
+    fn test_replacements() {
+        let state = StrategyState::default();
+        let email = "john@mail.com";
+        assert_eq!(replace("label", email, "email", &state), "[EMAIL]");
+        assert_eq!(replace("label:PII", email, "email", &state), "[PII]");
+        assert_eq!(replace("mask", email, "email", &state), "j***@****.com");
+        assert_eq!(replace("mask", "10.0.0.42", "ip", &state), "1*.*.*.**");
+        assert_eq!(
+            replace("mask", "eyJh.eyJz.c2ln", "jwt", &state),
+            "e***.****.****"
+        );
+        assert_eq!(
+            replace("keep-last:4", "9123-5678", "phone", &state),
+            "****-5678"
+        );
+        assert_eq!(replace("blackout", email, "email", &state), "█████");
+        assert_eq!(replace("blackout:2", email, "email", &state), "██");
+        assert_eq!(replace("remove", email, "email", &state), "");
+    }
+
+    #[test]
+    fn test_counter_is_stable_per_value() {
+        let state = StrategyState::default();
+        assert_eq!(replace("counter", "Tan", "person", &state), "<PERSON_1>");
+        assert_eq!(replace("counter", "Lim", "person", &state), "<PERSON_2>");
+        assert_eq!(replace("counter", "Tan", "person", &state), "<PERSON_1>");
+        assert_eq!(replace("counter", "a@b.com", "email", &state), "<EMAIL_1>");
+    }
+
+    #[test]
+    fn test_hmac_is_deterministic_per_key() {
+        let state = StrategyState::new(Some(b"secret".to_vec()), GeneratorRegistry::default());
+        let token = replace("hmac", "a@b.com", "email", &state);
+        assert_eq!(token.len(), "[REDACTED:]".len() + 16);
+        assert_eq!(replace("hmac", "a@b.com", "email", &state), token);
+        assert_ne!(replace("hmac", "c@d.com", "email", &state), token);
+
+        let other_key = StrategyState::new(Some(b"other".to_vec()), GeneratorRegistry::default());
+        assert_ne!(replace("hmac", "a@b.com", "email", &other_key), token);
+        assert_eq!(replace("hmac:8", "a@b.com", "email", &state).len(), 19);
+
+        let fpe_key: String = state
+            .mac(&["raf-fpe-key"])
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        for value in ["fpe", "raf-fpe-key", ""] {
+            assert_ne!(state.hmac_token(value, MAX_HMAC_LEN), fpe_key);
+        }
+    }
+
+    #[test]
+    fn test_fake_is_stable_per_value() {
+        let state = StrategyState::default();
+        let fake = replace("fake", "S1234567D", "nric", &state);
+        assert_ne!(fake, "S1234567D");
+        assert_eq!(fake.len(), 9);
+        assert_eq!(replace("fake", "S1234567D", "nric", &state), fake);
+
+        let keyed = |key: &[u8]| {
+            let state = StrategyState::new(Some(key.to_vec()), GeneratorRegistry::default());
+            replace("fake", "a@b.com", "email", &state)
+        };
+        assert_eq!(keyed(b"secret"), keyed(b"secret"));
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        for spec in [
+            "random",
+            "hmac:8",
+            "fake",
+            "fpe",
+            "label",
+            "label:X",
+            "mask",
+            "keep-last:4",
+            "blackout:5",
+        ] {
+            assert_eq!(spec.parse::<Strategy>().unwrap().to_string(), spec);
+        }
+        assert!("keep-last".parse::<Strategy>().is_err());
+        assert!("shred".parse::<Strategy>().is_err());
+        assert!("hmac:65".parse::<Strategy>().is_err());
+    }
+}
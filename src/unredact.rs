@@ -0,0 +1,312 @@
+use crate::error::{RafError, Result};
+use crate::format::FormatRegistry;
+use crate::mapping::{DocumentPart, RedactedData};
+use crate::output;
+use crate::redact;
+use crate::utils;
+use crate::vault::{self, VaultSecret};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Puts the original values of an unredact mapping back into the text of a
+/// redacted document.
+///
+/// Tokens are looked up wherever they are in the text, in any order. An
+/// entry with a recorded [`Location`](crate::mapping::Location) is only
+/// restored in the same document part, and only where its original value
+/// lands back on its recorded offset. This keeps apart the tokens that the
+/// `label` and `counter` strategies repeat, and ignores the same text written
+/// elsewhere. The offset may still be off when an earlier entry's token was
+/// deleted from the text, since that shifts everything after it, so such a
+/// shift is allowed. Entries without a location, as in version 1 mappings,
+/// are restored at the first occurrence of their token. Entries whose token
+/// is never found were altered or lost and end up in
+/// [`UnredactReport::missing`].
+#[derive(Debug, Clone)]
+pub struct Unredactor {
+    mapping: Vec<RedactedData>,
+    restored: Vec<bool>,
+    /// Indexes of the entries with a location, by document part.
+    located: HashMap<Option<DocumentPart>, Vec<usize>>,
+    /// Indexes of the entries without a location.
+    unlocated: Vec<usize>,
+    report: UnredactReport,
+}
+
+/// What [`Unredactor`] restored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnredactReport {
+    /// Number of tokens replaced with their original value.
+    pub restored: usize,
+    /// Mapping entries whose token was not found where expected.
+    pub missing: Vec<RedactedData>,
+}
+
+impl UnredactReport {
+    /// Returns `true` if every token of the mapping was restored.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl Unredactor {
+    pub fn new(mapping: Vec<RedactedData>) -> Self {
+        let mut located: HashMap<Option<DocumentPart>, Vec<usize>> = HashMap::new();
+        let mut unlocated = Vec::new();
+        for (idx, data) in mapping.iter().enumerate() {
+            match &data.location {
+                Some(location) => located.entry(location.part.clone()).or_default().push(idx),
+                None => unlocated.push(idx),
+            }
+        }
+        Unredactor {
+            restored: vec![false; mapping.len()],
+            mapping,
+            located,
+            unlocated,
+            report: UnredactReport::default(),
+        }
+    }
+
+    /// Returns `text` with the tokens of the mapping it contains replaced by
+    /// their original values.
+    pub fn restore_text(&mut self, text: &str) -> String {
+        self.restore_text_in(text, None)
+    }
+
+    /// Returns `text`, the `part` of a larger document, with the tokens of
+    /// the mapping it contains replaced by their original values.
+    pub fn restore_part(&mut self, text: &str, part: DocumentPart) -> String {
+        self.restore_text_in(text, Some(part))
+    }
+
+    fn restore_text_in(&mut self, text: &str, part: Option<DocumentPart>) -> String {
+        // Every occurrence of the token of every candidate entry, as its
+        // offset, length and entry, the longest first at each offset.
+        let located = self
+            .located
+            .get(&part)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut found: Vec<(usize, usize, usize)> = Vec::new();
+        for &entry in located.iter().chain(&self.unlocated) {
+            if self.restored[entry] {
+                continue;
+            }
+            for token in tokens(&self.mapping[entry]) {
+                found.extend(
+                    text.match_indices(token.as_str())
+                        .map(|(start, _)| (start, token.len(), entry)),
+                );
+            }
+        }
+        found.sort_by_key(|&(start, len, entry)| (start, Reverse(len), entry));
+        let present: HashSet<usize> = found.iter().map(|&(_, _, entry)| entry).collect();
+
+        let mut restored = String::with_capacity(text.len());
+        let mut cursor = 0;
+        // How far the restored text is off the recorded offsets, because of
+        // lost tokens, and where the last located entry restored ended.
+        let mut drift = 0;
+        let mut last_end = 0;
+        for (start, len, entry) in found {
+            if start < cursor || self.restored[entry] {
+                continue;
+            }
+            let data = &self.mapping[entry];
+            if let Some(location) = &data.location {
+                // The original goes back where it was found when redacting,
+                // unless entries expected before it were lost.
+                let offset = (restored.len() + start - cursor) as isize;
+                let expected = location.start as isize + drift;
+                if offset != expected {
+                    let lost_before = located.iter().any(|&other| {
+                        let other_start = self.mapping[other].location.as_ref().unwrap().start;
+                        other != entry
+                            && !self.restored[other]
+                            && !present.contains(&other)
+                            && (last_end..location.start).contains(&other_start)
+                    });
+                    if !lost_before {
+                        continue;
+                    }
+                    drift = offset - location.start as isize;
+                }
+                last_end = location.end;
+            }
+            restored.push_str(&text[cursor..start]);
+            restored.push_str(&data.unredacted_text);
+            cursor = start + len;
+            self.restored[entry] = true;
+            self.report.restored += 1;
+        }
+        restored.push_str(&text[cursor..]);
+        restored
+    }
+
+    /// Ends the restoration, reporting the tokens that were never found.
+    pub fn finish(mut self) -> UnredactReport {
+        let missing = self
+            .mapping
+            .into_iter()
+            .zip(self.restored)
+            .filter(|(_, restored)| !restored)
+            .map(|(data, _)| data);
+        self.report.missing.extend(missing);
+        self.report
+    }
+}
+
+/// The ways the token of `data` can be written in the document: wrapped by
+/// the `random` and `hmac` strategies, or as it is by the others.
+fn tokens(data: &RedactedData) -> Vec<String> {
+    if data.redacted_text.is_empty() {
+        return Vec::new();
+    }
+    vec![
+        format!("[REDACTED:{}]", data.redacted_text),
+        data.redacted_text.clone(),
+    ]
+}
+
+/// The `<stem>-unredact.json` mapping next to the redacted file at `path`,
+/// where raf writes it by default, or the `<stem>-unredact.vault` there if
+/// only the latter exists.
+pub fn mapping_path_for(path: &Path) -> Result<PathBuf> {
+    let json = utils::get_mapping_file_path(path, path.parent().unwrap_or_else(|| Path::new("")))?;
+    let vault = json.with_extension("vault");
+    Ok(if !json.exists() && vault.exists() {
+        vault
+    } else {
+        json
+    })
+}
+
+/// Where the original of the redacted file at `path` is restored by default:
+/// an `unredacted` folder next to it.
+pub fn output_path_for(path: &Path) -> Result<PathBuf> {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    utils::get_output_file_path(path, &parent.join("unredacted"))
+}
+
+/// Restores the original of the redacted file at `path` with the mapping at
+/// `mapping`, decrypted with `secret` if it is a vault, writing it to
+/// `output`.
+pub fn unredact_file(
+    path: &Path,
+    mapping: &Path,
+    secret: Option<&VaultSecret>,
+    output: &Path,
+    registry: &FormatRegistry,
+) -> Result<UnredactReport> {
+    let mapping = vault::read_mapping(mapping, secret)?;
+    restore_file(path, mapping, output, registry)
+}
+
+/// Restores the original of the redacted file at `path` with `mapping`, e.g.
+/// read from a [`MappingIndex`](crate::store::MappingIndex), writing it to
+/// `output`.
+pub fn restore_file(
+    path: &Path,
+    mapping: Vec<RedactedData>,
+    output: &Path,
+    registry: &FormatRegistry,
+) -> Result<UnredactReport> {
+    let (input, format) = redact::read_input(path, registry)?;
+    let mut unredactor = Unredactor::new(mapping);
+
+    let mut restored = Vec::new();
+    format.unredact(&input, &mut unredactor, &mut restored)?;
+    output::create_parent_dir(output)?;
+    fs::write(output, restored).map_err(|source| RafError::Io {
+        path: output.to_path_buf(),
+        source,
+    })?;
+    Ok(unredactor.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Redactor, Strategy};
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let redactor = Redactor::builder()
+            .patterns([r"\d{8}", r"\w+@\w+\.com"])
+            .build()?;
+        let text = "call 91234567 or mail jo@ex.com, not 91234567";
+        let (redacted, mapping) = redactor.redact_text(text)?;
+
+        let mut unredactor = Unredactor::new(mapping);
+        assert_eq!(unredactor.restore_text(&redacted), text);
+        let report = unredactor.finish();
+        assert_eq!(report.restored, 3);
+        assert!(report.is_complete());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_lost_tokens() -> Result<()> {
+        let redactor = Redactor::builder()
+            .patterns([r"\d{8}"])
+            .strategy(Strategy::Counter)
+            .build()?;
+        let (redacted, mapping) = redactor.redact_text("a 91234567 b 87654321 c 91234567")?;
+        assert_eq!(redacted, "a <REDACTED_1> b <REDACTED_2> c <REDACTED_1>");
+
+        // The second token was edited out of the document.
+        let mut unredactor = Unredactor::new(mapping);
+        let restored = unredactor.restore_text("a <REDACTED_1> b <REDACTED_?> c <REDACTED_1>");
+        assert_eq!(restored, "a 91234567 b <REDACTED_?> c 91234567");
+        let report = unredactor.finish();
+        assert_eq!(report.restored, 2);
+        let missing: Vec<_> = report
+            .missing
+            .iter()
+            .map(|data| (data.unredacted_text.as_str(), data.redacted_text.as_str()))
+            .collect();
+        assert_eq!(missing, [("87654321", "<REDACTED_2>")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checks_locations_of_labels() -> Result<()> {
+        let redactor = Redactor::builder()
+            .patterns([r"\w+@\w+\.com"])
+            .strategy(Strategy::Label(None))
+            .build()?;
+        let (redacted, mapping) = redactor.redact_text("mail jo@ex.com or al@ex.com")?;
+        assert_eq!(redacted, "mail [REDACTED] or [REDACTED]");
+
+        // A label typed in later is left alone.
+        let mut unredactor = Unredactor::new(mapping);
+        let edited = format!("{redacted}, [REDACTED]");
+        let restored = unredactor.restore_text(&edited);
+        assert_eq!(restored, "mail jo@ex.com or al@ex.com, [REDACTED]");
+        assert!(unredactor.finish().is_complete());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restores_version_1_mappings() -> anyhow::Result<()> {
+        let dir = Path::new("tests/test_files");
+        let output = std::env::temp_dir().join(format!("raf-unredact-{}", std::process::id()));
+        let formats = FormatRegistry::default();
+        for (name, restored) in [("file1.txt", 4), ("docx_1.docx", 15)] {
+            let path = dir.join("redacted").join(name);
+            let mapping = mapping_path_for(&path)?;
+            let output = output.join(name);
+            let report = unredact_file(&path, &mapping, None, &output, &formats)?;
+            assert!(report.is_complete(), "{name}: {:?}", report.missing);
+            assert_eq!(report.restored, restored);
+            if name.ends_with(".txt") {
+                assert_eq!(fs::read(&output)?, fs::read(dir.join(name))?);
+            }
+        }
+        fs::remove_dir_all(output)?;
+        Ok(())
+    }
+}
@@ -1,90 +1,169 @@
-use crate::utils;
-use crate::RED_ERROR_STRING;
-use anyhow::{anyhow, Result};
+use crate::error::{RafError, Result};
+use crate::mapping::{DocumentPart, Location, MatchContext, RedactedData};
+use crate::matcher::{CompiledPattern, Matcher};
+use crate::patterns::{PatternSet, PatternSource};
 use rand::{distributions::Alphanumeric, Rng};
-use regex::Regex;
-use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::Write;
+#[cfg(test)]
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize, PartialEq, Serialize, Clone, Default)]
-pub struct RedactedData {
-    unredacted_text: String,
-    redacted_text: String,
-}
-
-#[derive(Debug, Deserialize, PartialEq, Serialize, Clone, Default)]
-pub struct Pattern {
-    pattern: String,
-    #[serde(rename = "type")]
-    types: Vec<String>,
-}
-
-pub(crate) fn get_patterns_from_json(json_file_content: String) -> Result<Vec<Pattern>> {
-    serde_json::from_str(&json_file_content).map_err(|err| {
-        anyhow!(
-            "{}Failed to read patterns from the content of the json file, {err}",
-            *RED_ERROR_STRING,
-        )
-    })
-}
-
-pub(crate) fn get_files_dirs_from_folder(
+/// Lists the files and sub-directories directly inside `path`, along with
+/// the entries that could not be read.
+pub fn get_files_dirs_from_folder(
     path: &Path,
-) -> Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<anyhow::Error>)> {
-    let entries = path.read_dir().map_err(|err| {
-        anyhow!(
-            "{}Directory: {} cannot be read, err = {err}",
-            *RED_ERROR_STRING,
-            path.display()
-        )
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<RafError>)> {
+    let entries = path.read_dir().map_err(|source| RafError::Io {
+        path: path.to_path_buf(),
+        source,
     })?;
     let (entries, errors): (Vec<_>, Vec<_>) = entries
         // https://doc.rust-lang.org/rust-by-example/error/iter_result.html
         .into_iter()
-        .partition(Result::is_ok);
+        .partition(std::io::Result::is_ok);
     let (files, dirs): (Vec<_>, Vec<_>) = entries
         .into_iter()
-        .map(Result::unwrap)
+        .map(std::io::Result::unwrap)
         .map(|entry| entry.path())
         .partition(|p| Path::is_file(p));
 
     let errors = errors
         .into_iter()
-        .map(Result::unwrap_err)
-        .map(|err| {
-            anyhow!(
-                "{}Directory Entry cannot be read, err = {err}",
-                *RED_ERROR_STRING
-            )
+        .map(std::io::Result::unwrap_err)
+        .map(|source| RafError::Io {
+            path: path.to_path_buf(),
+            source,
         })
         .collect();
     Ok((files, dirs, errors))
 }
 
+/// Replaces every match of `matcher` in `text` with the text returned by
+/// `replace`, which also returns the token recorded in the mapping.
+///
+/// All matches are collected against the original `text` first and overlaps
+/// resolved by the matcher's [`OverlapPolicy`](crate::OverlapPolicy), so the
+/// replacements can be spliced in a single left-to-right pass.
+///
+/// Each record is located in `text`, which is the `part` of its document,
+/// and given up to `context` characters of text around it if `context` is
+/// not `0`.
 pub(crate) fn redact_text_get_data(
     text: &str,
-    regex_vec: &[Regex],
+    part: Option<&DocumentPart>,
+    matcher: &Matcher,
+    context: usize,
+    mut replace: impl FnMut(&CompiledPattern, &str) -> Result<(String, String)>,
 ) -> Result<(String, Vec<RedactedData>)> {
-    let mut redacted_text = String::from(text);
+    let mut redacted_text = String::with_capacity(text.len());
     let mut redacted_data: Vec<RedactedData> = Vec::new();
-    for regex in regex_vec {
-        let matches: Vec<_> = regex.find_iter(text).collect();
-        for mat in matches.iter().rev() {
-            let randomized_str = randomize_string(mat.as_str());
-            let redacted_str = "[REDACTED:".to_string() + &randomized_str + "]";
-            redacted_text.replace_range(mat.start()..mat.end(), &redacted_str);
-            redacted_data.push(RedactedData {
-                unredacted_text: mat.as_str().to_owned(),
-                redacted_text: randomized_str,
+    // Byte ranges of the replacements in `redacted_text`.
+    let mut replaced = Vec::new();
+    let mut position = TextPosition::default();
+    let mut last_end = 0;
+    for mat in matcher.find(text) {
+        let pattern = &matcher.patterns()[mat.pattern];
+        let matched = &text[mat.start..mat.end];
+        let (redacted_str, token) = replace(pattern, matched)?;
+        redacted_text.push_str(&text[last_end..mat.start]);
+        let replaced_start = redacted_text.len();
+        redacted_text.push_str(&redacted_str);
+        replaced.push((replaced_start, redacted_text.len()));
+        last_end = mat.end;
+
+        position.advance(text, mat.start);
+        let (line, column, char_start) = (position.line, position.column, position.chars);
+        position.advance(text, mat.end);
+        redacted_data.push(RedactedData {
+            unredacted_text: matched.to_owned(),
+            redacted_text: token,
+            pattern_id: Some(matcher.pattern_id(mat.pattern)),
+            type_name: Some(pattern.type_name().to_owned()),
+            location: Some(Location {
+                start: mat.start,
+                end: mat.end,
+                char_start,
+                char_end: position.chars,
+                line,
+                column,
+                part: part.cloned(),
+            }),
+            context: None,
+        });
+    }
+    redacted_text.push_str(&text[last_end..]);
+
+    if context > 0 {
+        for (data, (start, end)) in redacted_data.iter_mut().zip(replaced) {
+            let location = data.location.as_ref().expect("records are located above");
+            let (before, after) = text_around(text, location.start, location.end, context);
+            let (redacted_before, redacted_after) =
+                text_around(&redacted_text, start, end, context);
+            data.context = Some(MatchContext {
+                before,
+                after,
+                redacted_before,
+                redacted_after,
+                replacement: redacted_text[start..end].to_owned(),
             });
         }
     }
     Ok((redacted_text, redacted_data))
 }
 
+/// Up to `chars` characters of `text` before byte `start` and after byte
+/// `end`.
+pub(crate) fn text_around(text: &str, start: usize, end: usize, chars: usize) -> (String, String) {
+    let before_start = text[..start]
+        .char_indices()
+        .rev()
+        .take(chars)
+        .last()
+        .map_or(start, |(idx, _)| idx);
+    let after_end = text[end..]
+        .char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(idx, _)| end + idx);
+    (
+        text[before_start..start].to_owned(),
+        text[end..after_end].to_owned(),
+    )
+}
+
+/// A position in a text, advanced from the start as matches are found.
+struct TextPosition {
+    byte: usize,
+    chars: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Default for TextPosition {
+    fn default() -> Self {
+        TextPosition {
+            byte: 0,
+            chars: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl TextPosition {
+    fn advance(&mut self, text: &str, to: usize) {
+        for c in text[self.byte..to].chars() {
+            self.chars += 1;
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.byte = to;
+    }
+}
+
 pub(crate) fn randomize_string(s: &str) -> String {
     rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -93,89 +172,59 @@ pub(crate) fn randomize_string(s: &str) -> String {
         .collect()
 }
 
-pub(crate) fn get_pattern_vec(pattern_file: &str, types: Vec<String>) -> Result<Vec<Regex>> {
-    let patterns_json_content = fs::read_to_string(pattern_file)
-        .map_err(|err| anyhow!("{}Cannot open {pattern_file}, {err}", *RED_ERROR_STRING))?;
-
-    let patterns = utils::get_patterns_from_json(patterns_json_content)?;
-
-    let filtered_patterns: Vec<Pattern> = patterns
-        .into_iter()
-        .filter(|p| p.types.iter().any(|t| types.contains(t)))
-        .collect();
-
-    let regex_vec: Vec<Regex> = filtered_patterns
-        .iter()
-        .map(|p| Regex::new(&p.pattern).expect("Invalid regex pattern."))
-        .collect();
-    Ok(regex_vec)
+/// Compiles the patterns in `pattern_file` whose `type` is one of `types`,
+/// without the built-in patterns, see [`PatternSet`].
+pub fn get_pattern_vec(pattern_file: &str, types: Vec<String>) -> Result<Vec<CompiledPattern>> {
+    let mut patterns = PatternSet::new();
+    let path = Path::new(pattern_file);
+    patterns.add_file(path, PatternSource::File(path.to_path_buf()))?;
+    patterns.compile(&types)
 }
 
-pub(crate) fn write_redacted_data_json(
-    all_redacted_data: Vec<RedactedData>,
-    path: &Path,
-    output_folder: &Path,
-) -> anyhow::Result<()> {
+/// Path of the `-unredact.json` mapping of `path` inside `mapping_folder`.
+pub(crate) fn get_mapping_file_path(path: &Path, mapping_folder: &Path) -> Result<PathBuf> {
     let mut redacted_json_data_file_path = path
         .file_stem()
-        .ok_or_else(|| anyhow!(
-            "{} Unable to get the `file_stem` of {}\n",
-            *RED_ERROR_STRING,
-            path.display(),
-        ))?
+        .ok_or_else(|| RafError::InvalidPath(path.to_path_buf()))?
         .to_os_string();
 
     redacted_json_data_file_path.push("-unredact.json");
-
-    let unredacted_file_path = output_folder.join(redacted_json_data_file_path);
-
-    let unredacted_file = fs::File::create(unredacted_file_path.clone()).map_err(|err| {
-        anyhow!(
-            "{}Failed to create file {:?}, {err}",
-            *RED_ERROR_STRING,
-            unredacted_file_path
-        )
-    })?;
-
-    serde_json::to_writer_pretty(unredacted_file, &all_redacted_data)
-        .map_err(|err| anyhow!("{}Failed to write file, {err}", *RED_ERROR_STRING))?;
-    anyhow::Ok(())
+    Ok(mapping_folder.join(redacted_json_data_file_path))
 }
 
-pub(crate) fn write_redacted_text<S: AsRef<[u8]>>(
-    redacted_text: S,
-    path: &Path,
-    output_folder: &Path,
-) -> anyhow::Result<()> {
-    let output_path = output_folder.join(path.file_name().ok_or_else(|| {
-        anyhow!(
-            "{} Unable to join {} with the `file_name` of {}",
-            *RED_ERROR_STRING,
-            output_folder.display(),
-            path.display()
-        )
-    })?);
-
-    let mut file = fs::File::create(output_path).map_err(|err| {
-        anyhow!(
-            "{}Unable to create the redacted text file, {err}",
-            *RED_ERROR_STRING
-        )
-    })?;
+/// Writes `contents` to `path`, making the file readable and writable by its
+/// owner only, as mappings hold the original values.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let io_error = |source| RafError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(io_error)?;
+    // The mode only applies to files created by `open`.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+        .map_err(io_error)?;
+    std::io::Write::write_all(&mut file, contents).map_err(io_error)
+}
 
-    file.write_all(redacted_text.as_ref()).map_err(|err| {
-        anyhow!(
-            "{}Unable to write the redacted text file, {err}",
-            *RED_ERROR_STRING
-        )
-    })?;
-    anyhow::Ok(())
+/// Path of the redacted counterpart of `path` inside `output_folder`.
+pub(crate) fn get_output_file_path(path: &Path, output_folder: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| RafError::InvalidPath(path.to_path_buf()))?;
+    Ok(output_folder.join(file_name))
 }
 
+#[cfg(test)]
 #[derive(Debug)]
-struct AnyhowErrVec(Vec<anyhow::Error>);
+struct RafErrorVec(Vec<RafError>);
 
-impl PartialEq for AnyhowErrVec {
+#[cfg(test)]
+impl PartialEq for RafErrorVec {
     fn eq(&self, other: &Self) -> bool {
         self.0.len() == other.0.len()
             && self
@@ -186,14 +235,16 @@ impl PartialEq for AnyhowErrVec {
     }
 }
 
-impl From<Vec<anyhow::Error>> for AnyhowErrVec {
-    fn from(vec: Vec<anyhow::Error>) -> Self {
-        AnyhowErrVec(vec)
+#[cfg(test)]
+impl From<Vec<RafError>> for RafErrorVec {
+    fn from(vec: Vec<RafError>) -> Self {
+        RafErrorVec(vec)
     }
 }
 
-impl Deref for AnyhowErrVec {
-    type Target = Vec<anyhow::Error>;
+#[cfg(test)]
+impl Deref for RafErrorVec {
+    type Target = Vec<RafError>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -204,54 +255,27 @@ impl Deref for AnyhowErrVec {
 mod tests {
 
     use super::*;
-    use crate::Pattern;
     use anyhow::Result;
     use std::path::PathBuf;
 
-    #[test]
-    fn test_get_patterns_from_json() -> Result<()> {
-        let json_file = r#"
-        [
-            {
-                "pattern": "\\d+",
-                "type": ["pattern1", "pattern12"]
-            },
-            {
-                "pattern": "\\w+",
-                "type": ["email", "emails"]
-            }
-        ]
-        "#;
-        let expected_patterns = vec![
-            Pattern {
-                types: vec!["pattern1".to_string(), "pattern12".to_string()],
-                pattern: "\\d+".to_owned(),
-            },
-            Pattern {
-                types: vec!["email".to_owned(), "emails".to_string()],
-                pattern: "\\w+".to_owned(),
-            },
-        ];
-        let actual_patterns = get_patterns_from_json(json_file.to_owned())?;
-        assert_eq!(expected_patterns, actual_patterns);
-        Ok(())
-    }
-
     #[test]
     fn test_get_files_from_folder() -> Result<()> {
-        let folder = "./tests/test_files";
-        let (expected_entries, expected_errors): (Vec<PathBuf>, Vec<anyhow::Error>) = (
+        let folder = Path::new("./tests/test_files");
+        let (expected_entries, expected_errors): (Vec<PathBuf>, Vec<RafError>) = (
             vec![
+                PathBuf::from("./tests/test_files/docx_1.docx"),
+                PathBuf::from("./tests/test_files/docx_1.pdf"),
                 PathBuf::from("./tests/test_files/file1.txt"),
-                PathBuf::from("./tests/test_files/file2.txt"),
+                PathBuf::from("./tests/test_files/noextension"),
             ],
             vec![],
         );
-        let (actual_entries, actual_errors) = get_files_from_folder(folder)?;
+        let (mut actual_entries, _, actual_errors) = get_files_dirs_from_folder(folder)?;
+        actual_entries.sort();
         assert_eq!(expected_entries, actual_entries);
         assert_eq!(
-            AnyhowErrVec::from(expected_errors),
-            AnyhowErrVec::from(actual_errors)
+            RafErrorVec::from(expected_errors),
+            RafErrorVec::from(actual_errors)
         );
         Ok(())
     }
@@ -259,8 +283,8 @@ mod tests {
     #[test]
     fn test_get_output_file_path() -> Result<()> {
         let file_path = PathBuf::from("path/to/file.txt");
-        let expected_output_path = PathBuf::from("file.txt-unredact.json");
-        let actual_output_path = get_output_file_path(&file_path)?;
+        let expected_output_path = PathBuf::from("path/redacted/file.txt");
+        let actual_output_path = get_output_file_path(&file_path, Path::new("path/redacted"))?;
         assert_eq!(expected_output_path, actual_output_path);
         Ok(())
     }
@@ -0,0 +1,284 @@
+//! Checks run on each match of a pattern before it is redacted, to drop
+//! matches that have the right shape but are not what the pattern looks
+//! for, e.g. an invoice number that looks like an NRIC but has the wrong
+//! checksum letter, or a long identifier that is not random enough to be a
+//! secret.
+
+use crate::fake::{luhn_check_digit, nric_checksum};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+/// A check of a matched value, set with the `validator` field of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Validator {
+    /// A Singapore NRIC or FIN with the right checksum letter.
+    Nric,
+    /// A card number of 12 to 19 digits passing the Luhn check, spaces and
+    /// hyphens aside.
+    Luhn,
+    /// An IBAN passing the mod-97 check, spaces aside.
+    Iban,
+    /// An ISBN-10 or ISBN-13 with the right check digit, spaces and hyphens
+    /// aside.
+    Isbn,
+    /// An IPv4 address, each part from 0 to 255 without leading zeros.
+    Ipv4,
+    /// A JSON Web Token whose header decodes to a JSON object with an
+    /// `alg`.
+    Jwt,
+    /// A random-looking token with a digit: hexadecimal with a Shannon
+    /// entropy of at least [`HEX_ENTROPY`] bits per character, or else at
+    /// least [`BASE64_ENTROPY`].
+    Entropy,
+}
+
+/// Entropy in bits per character from which a hexadecimal token is taken
+/// for a secret, out of at most 4.
+pub const HEX_ENTROPY: f64 = 3.0;
+
+/// Entropy in bits per character from which a base64 or other token is
+/// taken for a secret, out of at most 6.
+pub const BASE64_ENTROPY: f64 = 4.5;
+
+impl Validator {
+    /// Returns `true` if `value` passes the check.
+    pub fn is_valid(&self, value: &str) -> bool {
+        match self {
+            Validator::Nric => is_nric(value),
+            Validator::Luhn => is_luhn(value),
+            Validator::Iban => is_iban(value),
+            Validator::Isbn => is_isbn(value),
+            Validator::Ipv4 => Ipv4Addr::from_str(value).is_ok(),
+            Validator::Jwt => is_jwt(value),
+            Validator::Entropy => is_random(value),
+        }
+    }
+}
+
+impl FromStr for Validator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nric" => Ok(Validator::Nric),
+            "luhn" => Ok(Validator::Luhn),
+            "iban" => Ok(Validator::Iban),
+            "isbn" => Ok(Validator::Isbn),
+            "ipv4" => Ok(Validator::Ipv4),
+            "jwt" => Ok(Validator::Jwt),
+            "entropy" => Ok(Validator::Entropy),
+            _ => Err(format!(
+                "unknown validator `{s}`, expected `nric`, `luhn`, `iban`, `isbn`, `ipv4`, \
+                 `jwt` or `entropy`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Validator::Nric => "nric",
+            Validator::Luhn => "luhn",
+            Validator::Iban => "iban",
+            Validator::Isbn => "isbn",
+            Validator::Ipv4 => "ipv4",
+            Validator::Jwt => "jwt",
+            Validator::Entropy => "entropy",
+        })
+    }
+}
+
+/// The digits of `value`, or `None` if it has anything but digits and the
+/// `separators`.
+fn digits(value: &str, separators: &[char]) -> Option<Vec<u32>> {
+    value
+        .chars()
+        .filter(|c| !separators.contains(c))
+        .map(|c| c.to_digit(10))
+        .collect()
+}
+
+fn is_nric(value: &str) -> bool {
+    let value = value.to_ascii_uppercase();
+    let mut chars = value.chars();
+    let (Some(prefix), Some(checksum)) = (chars.next(), chars.next_back()) else {
+        return false;
+    };
+    match digits(chars.as_str(), &[]) {
+        Some(digits) if digits.len() == 7 && matches!(prefix, 'S' | 'T' | 'F' | 'G') => {
+            nric_checksum(prefix, &digits) == checksum
+        }
+        _ => false,
+    }
+}
+
+fn is_luhn(value: &str) -> bool {
+    match digits(value, &[' ', '-']) {
+        Some(digits) if (12..=19).contains(&digits.len()) => {
+            let (check, body) = digits.split_last().unwrap();
+            luhn_check_digit(body) == *check
+        }
+        _ => false,
+    }
+}
+
+fn is_iban(value: &str) -> bool {
+    let iban: Vec<char> = value
+        .chars()
+        .filter(|c| *c != ' ')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if !(15..=34).contains(&iban.len())
+        || !iban[..2].iter().all(char::is_ascii_uppercase)
+        || !iban[2..4].iter().all(char::is_ascii_digit)
+        || !iban.iter().all(char::is_ascii_alphanumeric)
+    {
+        return false;
+    }
+    // The country code and check digits move to the end, and letters count
+    // as two digits, A being 10.
+    let remainder =
+        iban[4..]
+            .iter()
+            .chain(&iban[..4])
+            .fold(0, |remainder, c| match c.to_digit(36) {
+                Some(n) if n > 9 => (remainder * 100 + n) % 97,
+                Some(n) => (remainder * 10 + n) % 97,
+                None => remainder,
+            });
+    remainder == 1
+}
+
+fn is_isbn(value: &str) -> bool {
+    let value = value.replace([' ', '-'], "");
+    // A Unicode `\d` also matches non-ASCII digits, whose bytes would throw
+    // off the length and the split below.
+    if !value.is_ascii() {
+        return false;
+    }
+    match value.len() {
+        10 => {
+            let (body, check) = value.split_at(9);
+            let check = match check {
+                "X" | "x" => Some(10),
+                _ => check.parse().ok(),
+            };
+            match (digits(body, &[]), check) {
+                (Some(body), Some(check)) => {
+                    let sum: u32 = body.iter().zip((2..=10).rev()).map(|(d, w)| d * w).sum();
+                    (sum + check).is_multiple_of(11)
+                }
+                _ => false,
+            }
+        }
+        13 => match digits(&value, &[]) {
+            Some(digits) => {
+                let sum: u32 = digits
+                    .iter()
+                    .zip([1, 3].iter().cycle())
+                    .map(|(d, w)| d * w)
+                    .sum();
+                sum.is_multiple_of(10)
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_jwt(value: &str) -> bool {
+    let mut parts = value.split('.');
+    let (Some(header), Some(_), Some(_), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    URL_SAFE_NO_PAD
+        .decode(header.trim_end_matches('='))
+        .ok()
+        .and_then(|json| serde_json::from_slice::<serde_json::Value>(&json).ok())
+        .is_some_and(|header| header.get("alg").is_some())
+}
+
+/// The Shannon entropy of the characters of `value`, in bits per character.
+pub fn shannon_entropy(value: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in value.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let len = value.chars().count() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn is_random(value: &str) -> bool {
+    let value = value.trim_end_matches('=');
+    if !value.chars().any(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let is_hex = value.chars().all(|c| c.is_ascii_hexdigit());
+    let threshold = if is_hex { HEX_ENTROPY } else { BASE64_ENTROPY };
+    shannon_entropy(value) >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JWT: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiIxMjM0NTY3ODkwIn0.\
+                       dozjgNryP4J3jVmNHl0w5N_XgL0n3I9PlFUP0THsR8U";
+
+    #[test]
+    fn test_validators() {
+        let cases = [
+            (Validator::Nric, "S1234567D", true),
+            (Validator::Nric, "S1234567A", false),
+            (Validator::Nric, "G1234567X", true),
+            (Validator::Luhn, "4111 1111 1111 1111", true),
+            (Validator::Luhn, "4111-1111-1111-1112", false),
+            (Validator::Luhn, "0000", false),
+            (Validator::Iban, "GB82 WEST 1234 5698 7654 32", true),
+            (Validator::Iban, "GB82 WEST 1234 5698 7654 33", false),
+            (Validator::Isbn, "0-306-40615-2", true),
+            (Validator::Isbn, "978-0-306-40615-7", true),
+            (Validator::Isbn, "978-0-306-40615-6", false),
+            (Validator::Isbn, "12345678\u{669}", false),
+            (Validator::Isbn, "0-306-40615-\u{662}", false),
+            (Validator::Ipv4, "192.168.0.1", true),
+            (Validator::Ipv4, "192.168.0.256", false),
+            (Validator::Ipv4, "01.2.3.4", false),
+            (Validator::Jwt, JWT, true),
+            (Validator::Jwt, "eyJub3QiOiJhIGp3dCJ9.e30.x", false),
+            (
+                Validator::Entropy,
+                "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+                true,
+            ),
+            (
+                Validator::Entropy,
+                "9a8f7c6e5d4b3a29180f7e6d5c4b3a2918273645",
+                true,
+            ),
+            (Validator::Entropy, "UserAccountService2Impl", false),
+            (Validator::Entropy, "aaaaaaaaaaaaaaaaaaaa1111", false),
+        ];
+        for (validator, value, valid) in cases {
+            assert_eq!(validator.is_valid(value), valid, "{validator} {value}");
+        }
+        assert_eq!("luhn".parse(), Ok(Validator::Luhn));
+        assert!("crc".parse::<Validator>().is_err());
+    }
+}
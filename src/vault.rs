@@ -0,0 +1,229 @@
+//! Unredact mappings encrypted at rest.
+//!
+//! A vault file holds the JSON mapping encrypted with AES-256-GCM, under a
+//! key derived from a passphrase with Argon2id or from the content of a key
+//! file. It is laid out as:
+//!
+//! | bytes | content |
+//! |---|---|
+//! | 8 | `RAFVAULT` |
+//! | 1 | version, `1` |
+//! | 1 | key derivation, `1` for a passphrase, `2` for a key file |
+//! | 16 | salt |
+//! | 12 | nonce |
+//! | rest | encrypted mapping and authentication tag |
+//!
+//! The header is authenticated along with the mapping.
+
+use crate::error::{RafError, Result};
+use crate::mapping::{self, RedactedData};
+use crate::utils;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"RAFVAULT";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 2 + SALT_LEN;
+
+/// The secret a vault is encrypted with.
+#[derive(Clone)]
+pub enum VaultSecret {
+    /// A passphrase, stretched with Argon2id.
+    Passphrase(String),
+    /// The content of a key file, which should hold at least 32 random bytes.
+    KeyFile(Vec<u8>),
+}
+
+impl VaultSecret {
+    fn kdf_id(&self) -> u8 {
+        match self {
+            VaultSecret::Passphrase(_) => 1,
+            VaultSecret::KeyFile(_) => 2,
+        }
+    }
+
+    fn derive_key(&self, salt: &[u8]) -> [u8; 32] {
+        let mut key = [0; 32];
+        match self {
+            VaultSecret::Passphrase(passphrase) => Argon2::default()
+                .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+                .expect("the salt and key lengths are valid for Argon2"),
+            VaultSecret::KeyFile(content) => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(salt)
+                    .expect("HMAC accepts any key length");
+                mac.update(content);
+                key.copy_from_slice(&mac.finalize().into_bytes());
+            }
+        }
+        key
+    }
+}
+
+impl fmt::Debug for VaultSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultSecret::Passphrase(_) => f.write_str("Passphrase(..)"),
+            VaultSecret::KeyFile(_) => f.write_str("KeyFile(..)"),
+        }
+    }
+}
+
+/// Writes mappings encrypted with one [`VaultSecret`].
+///
+/// The key is derived once, with a salt shared by all the files a vault
+/// writes, so that a folder run pays for Argon2 only once.
+#[derive(Clone)]
+pub struct MappingVault {
+    kdf_id: u8,
+    salt: [u8; SALT_LEN],
+    cipher: Aes256Gcm,
+}
+
+impl MappingVault {
+    pub fn new(secret: &VaultSecret) -> Self {
+        let mut salt = [0; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let key = secret.derive_key(&salt);
+        MappingVault {
+            kdf_id: secret.kdf_id(),
+            salt,
+            cipher: Aes256Gcm::new(&key.into()),
+        }
+    }
+
+    /// Encrypts `all_redacted_data` into a vault file at `path`, readable by
+    /// its owner only.
+    pub fn write(&self, all_redacted_data: &[RedactedData], path: &Path) -> Result<()> {
+        let json =
+            mapping::to_json(all_redacted_data).map_err(|source| RafError::MappingWrite {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let mut nonce = [0; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let mut vault = Vec::with_capacity(HEADER_LEN + NONCE_LEN + json.len() + 16);
+        vault.extend_from_slice(MAGIC);
+        vault.extend_from_slice(&[VERSION, self.kdf_id]);
+        vault.extend_from_slice(&self.salt);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &json,
+                    aad: &vault,
+                },
+            )
+            .expect("AES-GCM encrypts any mapping");
+        vault.extend_from_slice(&nonce);
+        vault.extend_from_slice(&ciphertext);
+
+        utils::write_private(path, &vault)
+    }
+}
+
+impl fmt::Debug for MappingVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappingVault").finish_non_exhaustive()
+    }
+}
+
+/// Returns `true` if the file at `path` is a vault rather than a plain JSON
+/// mapping.
+pub fn is_vault(path: &Path) -> bool {
+    let mut magic = [0; MAGIC.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == MAGIC
+}
+
+/// Reads the mapping at `path`, decrypting it with `secret` if it is a
+/// vault.
+pub fn read_mapping(path: &Path, secret: Option<&VaultSecret>) -> Result<Vec<RedactedData>> {
+    if !is_vault(path) {
+        return mapping::read_mapping(path);
+    }
+    let secret = secret.ok_or_else(|| RafError::VaultKeyMissing(path.to_path_buf()))?;
+    let vault = fs::read(path).map_err(|source| RafError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let invalid = || RafError::VaultInvalid(path.to_path_buf());
+    if vault.len() < HEADER_LEN + NONCE_LEN || vault[MAGIC.len()] != VERSION {
+        return Err(invalid());
+    }
+    if vault[MAGIC.len() + 1] != secret.kdf_id() {
+        return Err(RafError::VaultDecrypt(path.to_path_buf()));
+    }
+
+    let (header, rest) = vault.split_at(HEADER_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let key = secret.derive_key(&header[HEADER_LEN - SALT_LEN..]);
+    let json = Aes256Gcm::new(&key.into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| RafError::VaultDecrypt(path.to_path_buf()))?;
+    mapping::from_json(&json).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("raf-vault-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file1-unredact.vault");
+        let mapping = vec![RedactedData {
+            unredacted_text: "S1234567D".to_owned(),
+            redacted_text: "[NRIC]".to_owned(),
+            ..RedactedData::default()
+        }];
+
+        let secret = VaultSecret::Passphrase("correct horse".to_owned());
+        MappingVault::new(&secret).write(&mapping, &path)?;
+        assert!(is_vault(&path));
+        assert!(!fs::read(&path)
+            .unwrap()
+            .windows(9)
+            .any(|w| w == b"S1234567D"));
+        assert_eq!(read_mapping(&path, Some(&secret))?, mapping);
+
+        let wrong = VaultSecret::Passphrase("wrong horse".to_owned());
+        assert!(matches!(
+            read_mapping(&path, Some(&wrong)),
+            Err(RafError::VaultDecrypt(_))
+        ));
+        assert!(matches!(
+            read_mapping(&path, None),
+            Err(RafError::VaultKeyMissing(_))
+        ));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Runs the `raf` binary as a user would.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs `raf` with `args` on `stdin`, without the user's own patterns.
+fn raf(args: &[&str], stdin: impl AsRef<[u8]>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_raf"))
+        .args(args)
+        .env("RAF_CONFIG_DIR", env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("raf runs");
+    // raf may exit on bad arguments before reading its input.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_ref());
+    child.wait_with_output().expect("raf exits")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_keywords_adjust_the_default_min_score() {
+    let args = ["file", "-", "-t", "phone", "--format", "txt"];
+    let text = "Tel: 91234567\nNumber 81234567\nInvoice 61234567\n";
+    let redacted = stdout(&raf(&args, text));
+    assert!(!redacted.contains("91234567"));
+    assert!(!redacted.contains("81234567"));
+    assert!(redacted.contains("Invoice 61234567"));
+
+    let redacted = stdout(&raf(&[&args[..], &["--min-score", "0.9"]].concat(), text));
+    assert!(!redacted.contains("91234567"));
+    assert!(redacted.contains("Number 81234567"));
+}
+
+#[test]
+fn test_scan_skips_redacted_folders() {
+    let args = [
+        "scan",
+        "tests/test_files",
+        "-r",
+        "-t",
+        "emails",
+        "jwt",
+        "--report-format",
+        "csv",
+    ];
+    let report = stdout(&raf(&args, ""));
+    assert!(report.contains("tests/test_files/file1.txt,email,4,"));
+    assert!(!report.contains("redacted/"), "{report}");
+    // Masked samples keep the top-level domain of emails only.
+    assert!(report.contains("j***.***@*******.com"));
+}
+
+#[test]
+fn test_store_cannot_be_encrypted() {
+    let store = format!("{}/encrypted-store.jsonl", env!("CARGO_TARGET_TMPDIR"));
+    let args = [
+        "file",
+        "-",
+        "-t",
+        "emails",
+        "--mapping-store",
+        &store,
+        "--encrypt-mapping",
+    ];
+    let output = raf(&args, "mail jo@ex.com");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    assert!(!std::path::Path::new(&store).exists());
+}
+
+#[test]
+fn test_stdin_rejects_output_flags() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    for flags in [
+        &["--output", dir][..],
+        &["--in-place"],
+        &["--mapping-dir", dir],
+    ] {
+        let args = [&["file", "-", "-t", "emails"][..], flags].concat();
+        let output = raf(&args, "mail jo@ex.com");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("`{}` cannot be used", flags[0])),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn test_decrypt_fpe_tokens() {
+    let key = format!("{}/fpe.key", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&key, "secret\n").unwrap();
+    let args = [
+        "file",
+        "-",
+        "-t",
+        "nric",
+        "--format",
+        "txt",
+        "--strategy",
+        "fpe",
+        "--key-file",
+        &key,
+    ];
+    let redacted = stdout(&raf(&args, "S1234567D"));
+    assert_ne!(redacted, "S1234567D");
+    let decrypted = stdout(&raf(&["decrypt", "--key-file", &key, &redacted], ""));
+    assert_eq!(decrypted, "S1234567D\n");
+
+    let output = raf(&["decrypt", "--key-file", &key, "A-12"], "");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_stdin_docx() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let docx = std::fs::read("tests/test_files/docx_1.docx").unwrap();
+    let args = ["file", "-", "-t", "emails", "--format", "docx"];
+
+    let output = raf(&args, &docx);
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(b"PK"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("9 redaction(s) cannot be reversed"));
+    let redacted = format!("{dir}/stdin-redacted.docx");
+    std::fs::write(&redacted, &output.stdout).unwrap();
+    let report = stdout(&raf(
+        &["scan", &redacted, "-t", "emails", "--report-format", "csv"],
+        "",
+    ));
+    assert!(report.contains("stdin-redacted.docx,,0,,"), "{report}");
+
+    let mapping = format!("{dir}/stdin/unredact.json");
+    let _ = std::fs::remove_file(&mapping);
+    let mapped = [&args[..], &["--mapping", &mapping]].concat();
+    let output = raf(&mapped, &docx);
+    std::fs::write(&redacted, &output.stdout).unwrap();
+    let restored = format!("{dir}/stdin-restored.docx");
+    let args = [
+        "unredact",
+        &redacted,
+        "--mapping",
+        &mapping,
+        "-o",
+        &restored,
+        "--force",
+    ];
+    assert!(stdout(&raf(&args, "")).contains("Restored 9 value(s)"));
+
+    // The mapping is not overwritten without `--force`.
+    let output = raf(&mapped, &docx);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert!(output.stdout.is_empty());
+    stdout(&raf(&[&mapped[..], &["--force"]].concat(), &docx));
+}